@@ -0,0 +1,6 @@
+use scheduling::problem_2::gap::run_gap_report;
+
+fn main() {
+    env_logger::init();
+    run_gap_report();
+}
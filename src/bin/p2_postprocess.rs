@@ -0,0 +1,6 @@
+use scheduling::problem_2::postprocess::run_postprocess;
+
+fn main() {
+    env_logger::init();
+    run_postprocess();
+}
@@ -0,0 +1,7 @@
+use scheduling::problem_2::solve::PreemptiveSolver;
+use scheduling::run_solver;
+
+fn main() {
+    env_logger::init();
+    run_solver::<PreemptiveSolver>();
+}
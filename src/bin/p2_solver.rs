@@ -3,6 +3,5 @@ use scheduling::run_solver;
 
 fn main() {
     env_logger::init();
-    let solver = Solver {};
-    run_solver(solver);
+    run_solver::<Solver>();
 }
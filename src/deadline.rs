@@ -0,0 +1,138 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Wall-clock time budget of a solver run
+///
+/// Cheap to clone, every algorithm gets its own copy and polls `expired`.
+/// Clones and splits share a stop flag, so `stop` ends the whole run early
+#[derive(Clone, Debug)]
+pub struct Deadline {
+    /// moment the budget started
+    start: Instant,
+    /// moment the budget runs out, `None` when unlimited
+    end: Option<Instant>,
+    /// set once there is nothing left to search for
+    stopped: Arc<AtomicBool>,
+}
+
+impl Deadline {
+    /// Budget of `limit` counted from `start`
+    pub fn new(start: Instant, limit: Duration) -> Self {
+        Deadline {
+            start,
+            end: start.checked_add(limit),
+            stopped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Budget of `limit` counted from now
+    pub fn after(limit: Duration) -> Self {
+        Deadline::new(Instant::now(), limit)
+    }
+
+    /// Budget that never runs out
+    pub fn unlimited() -> Self {
+        Deadline {
+            start: Instant::now(),
+            end: None,
+            stopped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// True once the budget is spent or the run was stopped
+    pub fn expired(&self) -> bool {
+        self.stopped() || self.end.is_some_and(|end| Instant::now() >= end)
+    }
+
+    /// Expire this deadline and every clone of it now
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// True once `stop` was called
+    pub fn stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    /// Time since the budget started
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Time left, `Duration::MAX` when unlimited
+    pub fn remaining(&self) -> Duration {
+        match self.end {
+            Some(end) => end.saturating_duration_since(Instant::now()),
+            None => Duration::MAX,
+        }
+    }
+
+    /// Deadline after `fraction` of the remaining time, used to split the
+    /// budget between consecutive phases of a solver
+    pub fn split(&self, fraction: f64) -> Deadline {
+        match self.end {
+            Some(_) => Deadline {
+                start: self.start,
+                end: Some(Instant::now() + self.remaining().mul_f64(fraction.clamp(0.0, 1.0))),
+                stopped: Arc::clone(&self.stopped),
+            },
+            None => self.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn expires_with_the_budget() {
+        let spent = Deadline::after(Duration::ZERO);
+        assert!(spent.expired());
+        assert_eq!(spent.remaining(), Duration::ZERO);
+
+        let running = Deadline::after(HOUR);
+        assert!(!running.expired());
+        assert!(running.remaining() <= HOUR);
+        assert!(running.remaining() > HOUR / 2);
+
+        let unlimited = Deadline::unlimited();
+        assert!(!unlimited.expired());
+        assert_eq!(unlimited.remaining(), Duration::MAX);
+    }
+
+    #[test]
+    fn split_never_ends_after_its_parent() {
+        let parent = Deadline::after(HOUR);
+        for fraction in [-1.0, 0.0, 0.25, 0.5, 1.0, 2.0] {
+            let child = parent.split(fraction);
+            assert!(child.end.expect("Split of a limited deadline") <= parent.end.unwrap());
+            assert_eq!(child.start, parent.start);
+        }
+        assert!(parent.split(0.0).expired());
+        assert!(parent.split(0.5).remaining() <= HOUR / 2);
+
+        assert!(Deadline::after(Duration::ZERO).split(1.0).expired());
+        assert_eq!(Deadline::unlimited().split(0.5).remaining(), Duration::MAX);
+    }
+
+    #[test]
+    fn stop_expires_clones_and_splits() {
+        let parent = Deadline::unlimited();
+        let clone = parent.clone();
+        let split = parent.split(0.5);
+        let nested = Deadline::after(HOUR).split(0.5);
+        assert!(!split.stopped());
+
+        split.stop();
+        for deadline in [&parent, &clone, &split] {
+            assert!(deadline.stopped());
+            assert!(deadline.expired());
+        }
+        // an unrelated deadline has its own flag
+        assert!(!nested.expired());
+    }
+}
@@ -19,6 +19,8 @@ impl fmt::Display for ReadFileError {
     }
 }
 
+impl std::error::Error for ReadFileError {}
+
 pub fn read_from_file(file_path: &Path) -> Result<String, ReadFileError> {
     let bytes = fs::read(file_path).map_err(ReadFileError::IoError)?;
 
@@ -27,6 +29,8 @@ pub fn read_from_file(file_path: &Path) -> Result<String, ReadFileError> {
     Ok(content)
 }
 
+/// Write `data` to a temporary file next to `file_path` and rename it over
+/// the target, so readers never see a partially written file
 pub fn write_to_file(file_path: &Path, data: impl AsRef<[u8]>) {
     let parent_dir = file_path.parent().unwrap();
 
@@ -41,7 +45,15 @@ pub fn write_to_file(file_path: &Path, data: impl AsRef<[u8]>) {
         });
     }
 
-    if let Err(err) = fs::write(file_path, data) {
+    let mut tmp_name = file_path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = file_path.with_file_name(tmp_name);
+
+    if let Err(err) = fs::write(&tmp_path, data) {
+        eprintln!("Failed to write file '{}': {}", tmp_path.display(), err);
+        std::process::exit(1);
+    }
+    if let Err(err) = fs::rename(&tmp_path, file_path) {
         eprintln!("Failed to write file '{}': {}", file_path.display(), err);
         std::process::exit(1);
     }
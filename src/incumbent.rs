@@ -0,0 +1,172 @@
+use crate::deadline::Deadline;
+use crate::SchedulableSolution;
+use log::{debug, info};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Best solution found so far during a solver run
+///
+/// Shared between the solver, the periodic writer and the signal handler,
+/// so that a valid answer is on disk whenever the process stops.
+/// With a lower bound set, reaching it stops the run (see `stop_at`).
+pub struct Incumbent<S> {
+    state: Mutex<IncumbentState<S>>,
+}
+
+struct IncumbentState<S> {
+    /// best solution offered so far
+    best: Option<S>,
+    /// bumped on every improvement
+    version: u64,
+    /// version that was last written to disk
+    written_version: u64,
+    /// proven lower bound and the deadline stopped once it is reached
+    target: Option<(f64, Deadline)>,
+}
+
+/// Scores this close to the lower bound count as optimal
+const BOUND_TOLERANCE: f64 = 1e-6;
+
+impl<S: SchedulableSolution> Incumbent<S> {
+    pub fn new() -> Self {
+        Incumbent {
+            state: Mutex::new(IncumbentState {
+                best: None,
+                version: 0,
+                written_version: 0,
+                target: None,
+            }),
+        }
+    }
+
+    /// Keep `solution` if it scores better than the current incumbent
+    pub fn offer(&self, solution: S) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let improves = state
+            .best
+            .as_ref()
+            .is_none_or(|best| solution.score() < best.score());
+        if improves {
+            debug!("New incumbent with score {}", solution.score());
+            state.best = Some(solution);
+            state.version += 1;
+            state.stop_if_optimal();
+        }
+        improves
+    }
+
+    /// Stop `deadline` as soon as an incumbent scores `lower_bound`
+    pub fn stop_at(&self, lower_bound: f64, deadline: &Deadline) {
+        let mut state = self.state.lock().unwrap();
+        state.target = Some((lower_bound, deadline.clone()));
+        state.stop_if_optimal();
+    }
+
+    /// Lower bound passed to `stop_at`, if any
+    pub fn lower_bound(&self) -> Option<f64> {
+        let state = self.state.lock().unwrap();
+        state.target.as_ref().map(|(lower_bound, _)| *lower_bound)
+    }
+
+    /// Score of the current incumbent
+    pub fn score(&self) -> Option<f64> {
+        let state = self.state.lock().unwrap();
+        state.best.as_ref().map(|best| best.score())
+    }
+
+    /// Write the incumbent to `path` unless this version is already on disk
+    pub fn write(&self, path: &Path) -> crate::Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        if state.version == state.written_version {
+            return Ok(false);
+        }
+        if let Some(best) = &state.best {
+            best.to_file(path)?;
+            info!(
+                "Incumbent with score {} written to {:?}",
+                best.score(),
+                path
+            );
+        }
+        state.written_version = state.version;
+        Ok(true)
+    }
+}
+
+impl<S: SchedulableSolution> IncumbentState<S> {
+    fn stop_if_optimal(&self) {
+        if let (Some(best), Some((lower_bound, deadline))) = (&self.best, &self.target) {
+            if best.score() <= lower_bound + BOUND_TOLERANCE && !deadline.stopped() {
+                info!(
+                    "Incumbent with score {} reached the lower bound, stopping",
+                    best.score()
+                );
+                deadline.stop();
+            }
+        }
+    }
+}
+
+impl<S: SchedulableSolution> Default for Incumbent<S> {
+    fn default() -> Self {
+        Incumbent::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::models::Solution;
+
+    fn solution(score: f64) -> Solution {
+        Solution {
+            strategy: "test".to_string(),
+            score,
+            job_results: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn offer_keeps_only_strictly_better_solutions() {
+        let incumbent = Incumbent::new();
+        assert_eq!(incumbent.score(), None);
+        assert!(incumbent.offer(solution(10.0)));
+        assert!(!incumbent.offer(solution(10.0)));
+        assert!(!incumbent.offer(solution(12.0)));
+        assert_eq!(incumbent.score(), Some(10.0));
+        assert!(incumbent.offer(solution(7.0)));
+        assert_eq!(incumbent.score(), Some(7.0));
+    }
+
+    #[test]
+    fn reaching_the_lower_bound_stops_the_run() {
+        let deadline = Deadline::unlimited();
+        let solver_copy = deadline.split(0.5);
+        let incumbent = Incumbent::new();
+        incumbent.stop_at(5.0, &deadline);
+        assert_eq!(incumbent.lower_bound(), Some(5.0));
+
+        incumbent.offer(solution(8.0));
+        assert!(!deadline.stopped());
+        incumbent.offer(solution(5.0 + BOUND_TOLERANCE / 2.0));
+        assert!(deadline.stopped());
+        assert!(solver_copy.expired());
+
+        // a bound set after it is reached stops the run at once
+        let late = Deadline::unlimited();
+        incumbent.stop_at(6.0, &late);
+        assert!(late.stopped());
+    }
+
+    #[test]
+    fn write_skips_versions_already_on_disk() {
+        let path = std::env::temp_dir().join(format!("incumbent_{}.txt", std::process::id()));
+        let incumbent = Incumbent::new();
+        assert!(incumbent.offer(solution(3.0)));
+        assert!(incumbent.write(&path).unwrap());
+        assert!(!incumbent.write(&path).unwrap());
+        assert!(incumbent.offer(solution(2.0)));
+        assert!(incumbent.write(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
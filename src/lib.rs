@@ -1,13 +1,21 @@
-use clap::{command, Parser};
-use log::info;
+use clap::{Args, Parser};
+use deadline::Deadline;
+use incumbent::Incumbent;
+use log::{error, info, warn};
 use std::error::Error;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+pub mod deadline;
 pub mod file_handler;
+pub mod incumbent;
 pub mod problem_2;
 
-///
-/// Library generic traits
-///
+//
+// Library generic traits
+//
 
 /// A generic result type for your library
 pub type Result<T> = std::result::Result<T, Box<dyn Error>>;
@@ -16,10 +24,16 @@ pub type Result<T> = std::result::Result<T, Box<dyn Error>>;
 pub trait SchedulableProblem: Sized {
     fn from_file(path: &Path) -> Result<Self>;
     fn to_file(&self, path: &Path) -> Result<()>;
+    /// Proven lower bound on the objective of any solution, if known
+    fn lower_bound(&self) -> Option<f64> {
+        None
+    }
 }
 
 pub trait SchedulableSolution: Sized {
     type Problem: SchedulableProblem;
+    /// Objective value stored in the solution (lower is better)
+    fn score(&self) -> f64;
     fn calculate_score(&self, instance: &Self::Problem) -> i64;
     fn from_file(path: &Path) -> Result<Self>;
     fn to_file(&self, path: &Path) -> Result<()>;
@@ -45,16 +59,37 @@ pub trait ProblemVerifier {
 
 pub trait ProblemSolver<'a> {
     type Problem: SchedulableProblem;
-    type Solution: SchedulableSolution;
+    type Solution: SchedulableSolution + Send + 'static;
+
+    /// Time budget used when none is given on the command line
+    fn default_time_limit(&self, problem: &Self::Problem) -> Duration;
+
+    /// Solve the problem, returning the best solution found before `deadline`.
+    /// Intermediate solutions offered to `incumbent` are kept on disk in case
+    /// the process gets killed.
+    fn solve(
+        &self,
+        problem: &mut Self::Problem,
+        deadline: &Deadline,
+        incumbent: &Incumbent<Self::Solution>,
+    ) -> Self::Solution;
+}
 
-    fn solve(&self, problem: &mut Self::Problem) -> Self::Solution;
+/// Relative gap of `score` over `lower_bound` in percent
+/// (absolute difference when the bound is 0)
+pub fn format_gap(score: f64, lower_bound: f64) -> String {
+    if lower_bound.abs() < 1e-9 {
+        format!("+{:.2}", score - lower_bound)
+    } else {
+        format!("{:.2}%", (score - lower_bound) / lower_bound * 100.0)
+    }
 }
 
 /// Drivers
 
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
-struct SolverArgs {
+#[command(version, about, long_about = None, after_help = INTERRUPT_HELP)]
+struct SolverArgs<S: Args> {
     /// Instance input file path
     #[arg(short, long)]
     input_instance: PathBuf,
@@ -62,6 +97,14 @@ struct SolverArgs {
     /// Solution output file path
     #[arg(short, long)]
     output_file: PathBuf,
+
+    /// Time limit in seconds (defaults to the limit of the problem)
+    #[arg(short, long)]
+    time_limit: Option<f64>,
+
+    /// Options of the solver implementation
+    #[command(flatten)]
+    solver: S,
 }
 
 /// Generator program for scheduling problems
@@ -119,6 +162,7 @@ where
 pub fn run_verifier<V>(verifier_implementation: V)
 where
     V: ProblemVerifier,
+    V::Solution: SchedulableSolution<Problem = V::Problem>,
 {
     let args = VerifierArgs::parse();
     println!(
@@ -138,6 +182,16 @@ where
                 V::Solution::from_file(solution_file).expect("Failed to load solution from file");
             if verifier_implementation.verify_solution(&problem, &solution) {
                 println!("Both instance and solution are valid");
+                let score = solution.calculate_score(&problem) as f64;
+                match problem.lower_bound() {
+                    Some(lower_bound) => println!(
+                        "score: {}, lower_bound: {:.2}, gap: {}",
+                        score,
+                        lower_bound,
+                        format_gap(score, lower_bound)
+                    ),
+                    None => println!("score: {}", score),
+                }
             } else {
                 println!("Instance or solution is invalid");
             }
@@ -149,16 +203,29 @@ where
             } else {
                 println!("Instance is invalid");
             }
-            return;
         }
     };
 }
 
-pub fn run_solver<S>(solver_implementation: S)
+/// How often the solver driver writes an improved incumbent to disk
+const INCUMBENT_WRITE_INTERVAL: Duration = Duration::from_millis(250);
+/// Exit status after SIGINT, SIGTERM or SIGHUP; the handler cannot tell them
+/// apart, so it does not use the 128 + signal convention
+const INTERRUPTED_EXIT_CODE: i32 = 3;
+/// Note on interrupted runs shown by `--help` of every solver
+const INTERRUPT_HELP: &str = "On SIGINT, SIGTERM or SIGHUP the solver stops, leaves the best \
+solution found so far in the output file and exits with status 3.";
+
+/// Solver program for scheduling problems
+///
+/// The solver implementation is configured from the command line
+pub fn run_solver<S>()
 where
-    S: for<'a> ProblemSolver<'a>,
+    S: for<'a> ProblemSolver<'a> + Args,
 {
-    let args = SolverArgs::parse();
+    let start = Instant::now();
+    let args = SolverArgs::<S>::parse();
+    let solver_implementation = args.solver;
     info!(
         "Running solver with input: {:?}, output: {:?}",
         args.input_instance, args.output_file
@@ -168,13 +235,69 @@ where
     let mut problem =
         S::Problem::from_file(&args.input_instance).expect("Failed to load problem from file");
 
-    // Solve problem
-    let solution = solver_implementation.solve(&mut problem);
+    // Time budget counts from program start
+    let time_limit = match args.time_limit {
+        Some(seconds) => Duration::from_secs_f64(seconds),
+        None => solver_implementation.default_time_limit(&problem),
+    };
+    info!("Time limit: {:?}", time_limit);
+    let deadline = Deadline::new(start, time_limit);
+    let lower_bound = problem.lower_bound();
 
-    // Save solution
-    solution
-        .to_file(&args.output_file)
+    // Best solution so far, written on SIGINT / SIGTERM
+    let incumbent = Arc::new(Incumbent::<S::Solution>::new());
+    if let Some(lower_bound) = lower_bound {
+        info!("Lower bound: {}", lower_bound);
+        incumbent.stop_at(lower_bound, &deadline);
+    }
+    {
+        let incumbent = Arc::clone(&incumbent);
+        let output_file = args.output_file.clone();
+        ctrlc::set_handler(move || {
+            warn!("Interrupted, saving incumbent to {:?}", output_file);
+            if let Err(err) = incumbent.write(&output_file) {
+                error!("Failed to save incumbent: {}", err);
+            }
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        })
+        .expect("Failed to set signal handler");
+    }
+
+    // Solve problem, periodically saving the incumbent
+    let finished = AtomicBool::new(false);
+    let solution = thread::scope(|scope| {
+        let writer = scope.spawn(|| {
+            while !finished.load(Ordering::Acquire) {
+                thread::park_timeout(INCUMBENT_WRITE_INTERVAL);
+                if let Err(err) = incumbent.write(&args.output_file) {
+                    error!("Failed to save incumbent: {}", err);
+                }
+            }
+        });
+
+        let solution = solver_implementation.solve(&mut problem, &deadline, &incumbent);
+        finished.store(true, Ordering::Release);
+        writer.thread().unpark();
+        solution
+    });
+
+    // Save solution (or an incumbent that scored better)
+    incumbent.offer(solution);
+    incumbent
+        .write(&args.output_file)
         .expect("Failed to save solution to file");
 
-    info!("Solution saved to {:?}", args.output_file);
+    info!(
+        "Solution saved to {:?} after {:?}",
+        args.output_file,
+        deadline.elapsed()
+    );
+    if let (Some(score), Some(lower_bound)) = (incumbent.score(), lower_bound) {
+        info!(
+            "score: {}, lower_bound: {}, gap: {}",
+            score,
+            lower_bound,
+            format_gap(score, lower_bound)
+        );
+    }
 }
@@ -0,0 +1,326 @@
+use crate::deadline::Deadline;
+use crate::problem_2::algo::des::{best_dispatch, ScheduleResult};
+use crate::problem_2::algo::portfolio::portfolio;
+use crate::problem_2::models::*;
+use crate::problem_2::schedule::Schedule;
+use clap::Args;
+use log::debug;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::time::Duration;
+
+/// Minimal decrease of late work accepted as a new best
+const IMPROVEMENT_EPS: f64 = 1e-9;
+
+/// Parameters of the ant colony system
+#[derive(Args, Clone)]
+pub struct AcoConfig {
+    /// Ants per iteration (they use the portfolio rules in turn as visibility)
+    #[arg(long = "aco-ants", default_value_t = 10)]
+    pub ants: usize,
+
+    /// Weight of the pheromone
+    #[arg(long = "aco-alpha", default_value_t = 1.0)]
+    pub alpha: f64,
+
+    /// Weight of the visibility
+    #[arg(long = "aco-beta", default_value_t = 2.0)]
+    pub beta: f64,
+
+    /// Probability of taking the best job instead of a roulette draw
+    #[arg(long = "aco-q0", default_value_t = 0.9)]
+    pub q0: f64,
+
+    /// Evaporation of the global pheromone update
+    #[arg(long = "aco-evaporation", default_value_t = 0.1)]
+    pub evaporation: f64,
+
+    /// Decay of the local pheromone update after every ant step
+    #[arg(long = "aco-local-decay", default_value_t = 0.1)]
+    pub local_decay: f64,
+
+    /// Weight of the best-so-far ant in the global update (elitist ant)
+    #[arg(long = "aco-elitist", default_value_t = 2.0)]
+    pub elitist: f64,
+
+    /// Number of iterations when the time budget is unlimited
+    /// (otherwise the search runs until the deadline)
+    #[arg(id = "aco_iterations", long = "aco-iterations", default_value_t = 200)]
+    pub iterations: u64,
+}
+
+impl Default for AcoConfig {
+    fn default() -> Self {
+        AcoConfig {
+            ants: 10,
+            alpha: 1.0,
+            beta: 2.0,
+            q0: 0.9,
+            evaporation: 0.1,
+            local_decay: 0.1,
+            elitist: 2.0,
+            iterations: 200,
+        }
+    }
+}
+
+/// Pheromone on (predecessor on the same machine, job); predecessor `n`
+/// stands for the start of a machine
+struct Pheromone {
+    n: usize,
+    initial: f64,
+    trail: Vec<f64>,
+}
+
+impl Pheromone {
+    fn new(n: usize, initial: f64) -> Self {
+        Pheromone {
+            n,
+            initial,
+            trail: vec![initial; (n + 1) * n],
+        }
+    }
+
+    fn get(&self, predecessor: Option<usize>, job_idx: usize) -> f64 {
+        self.trail[predecessor.unwrap_or(self.n) * self.n + job_idx]
+    }
+
+    /// Move the trail of an edge towards `target` by `rate`
+    fn blend(&mut self, predecessor: Option<usize>, job_idx: usize, rate: f64, target: f64) {
+        let trail = &mut self.trail[predecessor.unwrap_or(self.n) * self.n + job_idx];
+        *trail = (1.0 - rate) * *trail + rate * target;
+    }
+
+    /// Global update along the machine sequences of `schedule`
+    fn reinforce(&mut self, schedule: &Schedule, rate: f64, deposit: f64) {
+        for sequence in schedule.sequences() {
+            let mut predecessor = None;
+            for &job_idx in sequence {
+                self.blend(predecessor, job_idx, rate, deposit);
+                predecessor = Some(job_idx);
+            }
+        }
+    }
+}
+
+/// One ant: dispatches jobs like `run_simulation` (earliest free machine
+/// takes a released job, idling until the next release when there is none),
+/// choosing the job by pheromone and the visibility of `rule`
+fn construct(
+    jobs: &[Job],
+    machines: &[Machine],
+    rule: &dyn PriorityRule,
+    pheromone: &mut Pheromone,
+    config: &AcoConfig,
+    rng: &mut ChaCha8Rng,
+) -> Schedule {
+    let n = jobs.len();
+    let mut by_release: Vec<usize> = (0..n).collect();
+    by_release.sort_by_key(|&idx| (jobs[idx].r_j, idx));
+    let mut next_release = 0;
+    let mut released: Vec<usize> = Vec::new();
+    let mut sequences: Vec<Vec<usize>> = vec![Vec::new(); machines.len()];
+    let mut machine_times: Vec<f64> = vec![0.0; machines.len()];
+    let mut priorities = vec![0.0; n];
+    let mut attractiveness = vec![0.0; n];
+
+    while next_release < n || !released.is_empty() {
+        let k = (0..machines.len())
+            .min_by(|&a, &b| machine_times[a].total_cmp(&machine_times[b]))
+            .expect("Instance has no machines");
+        let mut t_free = machine_times[k];
+        if released.is_empty() {
+            t_free = t_free.max(jobs[by_release[next_release]].r_j as f64);
+        }
+        while next_release < n && jobs[by_release[next_release]].r_j as f64 <= t_free {
+            released.push(by_release[next_release]);
+            next_release += 1;
+        }
+        let predecessor = sequences[k].last().copied();
+
+        // visibility: higher priority is better, as in run_simulation,
+        // scaled by the average distance to the best candidate
+        let mut best_priority = f64::MIN;
+        for &job_idx in &released {
+            priorities[job_idx] = rule.calculate(t_free, &jobs[job_idx], &machines[k]);
+            best_priority = best_priority.max(priorities[job_idx]);
+        }
+        let spread = released
+            .iter()
+            .map(|&job_idx| best_priority - priorities[job_idx])
+            .sum::<f64>()
+            / released.len() as f64;
+        let spread = if spread > 0.0 { spread } else { 1.0 };
+
+        let mut total = 0.0;
+        let mut best: Option<(f64, usize)> = None;
+        for (idx, &job_idx) in released.iter().enumerate() {
+            let visibility = 1.0 / (1.0 + (best_priority - priorities[job_idx]) / spread);
+            let value = pheromone.get(predecessor, job_idx).powf(config.alpha)
+                * visibility.powf(config.beta);
+            attractiveness[idx] = value;
+            total += value;
+            if best.is_none_or(|(best_value, _)| value > best_value) {
+                best = Some((value, idx));
+            }
+        }
+
+        let idx = if rng.random::<f64>() < config.q0 || total <= 0.0 {
+            best.expect("Released jobs are not empty").1
+        } else {
+            let mut pick = rng.random::<f64>() * total;
+            (0..released.len())
+                .find(|&idx| {
+                    pick -= attractiveness[idx];
+                    pick <= 0.0
+                })
+                .unwrap_or(released.len() - 1)
+        };
+
+        let job_idx = released.swap_remove(idx);
+        pheromone.blend(predecessor, job_idx, config.local_decay, pheromone.initial);
+        machine_times[k] = jobs[job_idx].completion_time(&machines[k], t_free);
+        sequences[k].push(job_idx);
+    }
+
+    Schedule::new(jobs, machines, sequences)
+}
+
+/// Ant colony system over job dispatch sequences.
+///
+/// Ants build schedules with the same dispatcher as `run_simulation`; at every
+/// step the next released job of the earliest free machine is drawn by
+/// pheromone on (machine predecessor, job) and the visibility of a portfolio
+/// rule, each ant using the rules in turn. Every step applies the local pheromone update;
+/// after each iteration the iteration-best ant and, with weight `elitist`, the
+/// best-so-far ant reinforce their edges. The best schedule of the dispatch
+/// portfolio is the initial best, so the result is never worse than it.
+pub fn run_aco(
+    jobs: &[Job],
+    machines: &[Machine],
+    config: &AcoConfig,
+    seed: u64,
+    bound: f64,
+    deadline: &Deadline,
+) -> ScheduleResult {
+    let rules = portfolio();
+    let greedy = best_dispatch(jobs, machines, 0.0);
+    let mut best = Schedule::from_schedule(jobs, machines, &greedy.schedule);
+    if jobs.is_empty() {
+        return best.to_result(jobs, machines, "ACO");
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut pheromone = Pheromone::new(
+        jobs.len(),
+        1.0 / (jobs.len() as f64 * (1.0 + best.total_cost())),
+    );
+    let unlimited = deadline.remaining() == Duration::MAX;
+    let mut iteration: u64 = 0;
+
+    'search: while !deadline.expired() && best.total_cost() > bound + IMPROVEMENT_EPS {
+        if unlimited && iteration >= config.iterations {
+            break;
+        }
+        iteration += 1;
+
+        let mut iteration_best: Option<Schedule> = None;
+        for ant in 0..config.ants.max(1) {
+            if deadline.expired() {
+                break 'search;
+            }
+            let rule = rules[ant % rules.len()].as_ref();
+            let schedule = construct(jobs, machines, rule, &mut pheromone, config, &mut rng);
+            if iteration_best
+                .as_ref()
+                .is_none_or(|current| schedule.total_cost() < current.total_cost())
+            {
+                iteration_best = Some(schedule);
+            }
+        }
+
+        let iteration_best = iteration_best.expect("Colony has no ants");
+        if iteration_best.total_cost() < best.total_cost() - IMPROVEMENT_EPS {
+            best = iteration_best.clone();
+        }
+        pheromone.reinforce(
+            &iteration_best,
+            config.evaporation,
+            1.0 / (1.0 + iteration_best.total_cost()),
+        );
+        pheromone.reinforce(
+            &best,
+            config.evaporation,
+            config.elitist / (1.0 + best.total_cost()),
+        );
+    }
+
+    debug!(
+        "ACO: {} iterations, {} -> {}",
+        iteration,
+        greedy.total_tardy_work,
+        best.total_cost()
+    );
+    best.to_result(jobs, machines, "ACO")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::testing::*;
+
+    #[test]
+    fn pheromone_stays_between_initial_and_deposits() {
+        let config = AcoConfig::default();
+        let rules = portfolio();
+        for seed in 0..5 {
+            let instance = random_instance(15, 3, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let initial = 1.0 / (jobs.len() as f64 * (1.0 + 100.0));
+            let mut pheromone = Pheromone::new(jobs.len(), initial);
+            // every update blends the trail towards the initial value or a deposit
+            let (mut low, mut high) = (initial, initial);
+            for _ in 0..30 {
+                for rule in &rules {
+                    let schedule = construct(
+                        jobs,
+                        machines,
+                        rule.as_ref(),
+                        &mut pheromone,
+                        &config,
+                        &mut rng,
+                    );
+                    let deposit = config.elitist / (1.0 + schedule.total_cost());
+                    low = low.min(deposit);
+                    high = high.max(deposit);
+                    pheromone.reinforce(&schedule, config.evaporation, deposit);
+                }
+                // relative slack for the rounding of the blends
+                let (low, high) = (low * (1.0 - 1e-12), high * (1.0 + 1e-12));
+                assert!(pheromone
+                    .trail
+                    .iter()
+                    .all(|&trail| low <= trail && trail <= high));
+            }
+        }
+    }
+
+    #[test]
+    fn aco_improves_on_the_portfolio_and_repeats() {
+        let config = AcoConfig {
+            iterations: 30,
+            ..AcoConfig::default()
+        };
+        for seed in 0..10 {
+            let instance = random_instance(20, 3, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            let start = best_dispatch(jobs, machines, 0.0);
+            let run = || run_aco(jobs, machines, &config, seed, 0.0, &Deadline::unlimited());
+            let result = run();
+            assert_consistent(jobs, machines, &result.schedule, result.total_tardy_work);
+            assert!(result.total_tardy_work <= start.total_tardy_work + TOLERANCE);
+            assert_eq!(assignment(&run().schedule), assignment(&result.schedule));
+        }
+    }
+}
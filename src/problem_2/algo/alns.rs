@@ -0,0 +1,497 @@
+use crate::deadline::Deadline;
+use crate::problem_2::algo::des::ScheduleResult;
+use crate::problem_2::algo::portfolio::portfolio;
+use crate::problem_2::models::*;
+use crate::problem_2::schedule::Schedule;
+use clap::Args;
+use log::{debug, info};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::time::Duration;
+
+/// Minimal decrease of late work accepted as a new best
+const IMPROVEMENT_EPS: f64 = 1e-9;
+/// Score of an operator pair that found a new best schedule
+const SCORE_BEST: f64 = 33.0;
+/// Score of an operator pair that improved the current schedule
+const SCORE_IMPROVED: f64 = 9.0;
+/// Score of an operator pair whose worse schedule was accepted
+const SCORE_ACCEPTED: f64 = 13.0;
+/// Exponent biasing the worst-job removal towards the latest jobs
+const WORST_BIAS: i32 = 3;
+/// Final temperature as a fraction of the initial one
+const FINAL_TEMPERATURE: f64 = 1e-3;
+
+/// Parameters of the adaptive large neighbourhood search
+#[derive(Args, Clone)]
+pub struct AlnsConfig {
+    /// Fewest jobs removed by a destroy operator
+    #[arg(long = "alns-min-remove", default_value_t = 4)]
+    pub min_remove: usize,
+
+    /// Most jobs removed by a destroy operator
+    #[arg(long = "alns-max-remove", default_value_t = 30)]
+    pub max_remove: usize,
+
+    /// Number of machines compared by the regret insertion
+    #[arg(long = "alns-regret", default_value_t = 3)]
+    pub regret: usize,
+
+    /// Iterations between two updates of the operator weights
+    #[arg(long = "alns-segment", default_value_t = 100)]
+    pub segment: u64,
+
+    /// Share of the new segment score in the operator weight
+    #[arg(long = "alns-reaction", default_value_t = 0.1)]
+    pub reaction: f64,
+
+    /// Relative worsening accepted with probability 1/2 at the start
+    #[arg(long = "alns-start-worse", default_value_t = 0.05)]
+    pub start_worse: f64,
+
+    /// Number of iterations when the time budget is unlimited
+    /// (otherwise the search runs until the deadline)
+    #[arg(
+        id = "alns_iterations",
+        long = "alns-iterations",
+        default_value_t = 20_000
+    )]
+    pub iterations: u64,
+}
+
+impl Default for AlnsConfig {
+    fn default() -> Self {
+        AlnsConfig {
+            min_remove: 4,
+            max_remove: 30,
+            regret: 3,
+            segment: 100,
+            reaction: 0.1,
+            start_worse: 0.05,
+            iterations: 20_000,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Destroy {
+    /// Uniformly random jobs
+    Random,
+    /// Jobs with the most late work, randomised towards the latest
+    Worst,
+    /// Jobs starting one after another in time, over all machines
+    Segment,
+    /// Consecutive jobs of one machine (all of them when it holds few enough)
+    Machine,
+}
+
+const DESTROY: [Destroy; 4] = [
+    Destroy::Random,
+    Destroy::Worst,
+    Destroy::Segment,
+    Destroy::Machine,
+];
+
+#[derive(Clone, Copy)]
+enum Repair {
+    /// Jobs in random order, each at its cheapest position
+    Greedy,
+    /// Job with the largest regret over its best machines first
+    Regret,
+    /// Jobs in the order of a random portfolio rule, each after the jobs
+    /// finishing before its release date on the cheapest machine
+    Dispatch,
+}
+
+const REPAIR: [Repair; 3] = [Repair::Greedy, Repair::Regret, Repair::Dispatch];
+
+/// Adaptive weight and counters of one operator
+struct OperatorStats {
+    name: &'static str,
+    weight: f64,
+    segment_score: f64,
+    segment_uses: u64,
+    uses: u64,
+    new_best: u64,
+    improved: u64,
+    accepted: u64,
+}
+
+impl OperatorStats {
+    fn new(name: &'static str) -> Self {
+        OperatorStats {
+            name,
+            weight: 1.0,
+            segment_score: 0.0,
+            segment_uses: 0,
+            uses: 0,
+            new_best: 0,
+            improved: 0,
+            accepted: 0,
+        }
+    }
+
+    fn record(&mut self, score: f64) {
+        self.uses += 1;
+        self.segment_uses += 1;
+        self.segment_score += score;
+        if score == SCORE_BEST {
+            self.new_best += 1;
+        }
+        if score == SCORE_BEST || score == SCORE_IMPROVED {
+            self.improved += 1;
+        }
+        if score > 0.0 {
+            self.accepted += 1;
+        }
+    }
+
+    /// Blend the average score of the finished segment into the weight
+    fn update_weight(&mut self, reaction: f64) {
+        if self.segment_uses > 0 {
+            let score = self.segment_score / self.segment_uses as f64;
+            self.weight = ((1.0 - reaction) * self.weight + reaction * score).max(1e-3);
+        }
+        self.segment_score = 0.0;
+        self.segment_uses = 0;
+    }
+
+    fn log(&self) {
+        info!(
+            "ALNS {:>8}: weight {:>6.2}, used {:>6}, new best {:>4}, improved {:>5}, accepted {:>6}",
+            self.name, self.weight, self.uses, self.new_best, self.improved, self.accepted
+        );
+    }
+}
+
+/// Roulette wheel over operator weights
+fn select(operators: &[OperatorStats], rng: &mut ChaCha8Rng) -> usize {
+    let total: f64 = operators.iter().map(|op| op.weight).sum();
+    let mut pick = rng.random::<f64>() * total;
+    for (idx, op) in operators.iter().enumerate() {
+        pick -= op.weight;
+        if pick <= 0.0 {
+            return idx;
+        }
+    }
+    operators.len() - 1
+}
+
+/// Start time of every scheduled job as (start, machine, job)
+fn job_starts(schedule: &Schedule, jobs: &[Job], machines: &[Machine]) -> Vec<(f64, usize, usize)> {
+    let mut starts = Vec::with_capacity(jobs.len());
+    for (k, sequence) in schedule.sequences().iter().enumerate() {
+        for (i, &job_idx) in sequence.iter().enumerate() {
+            let start = schedule.completion_time(k, i) - jobs[job_idx].p_j as f64 * machines[k].b_k;
+            starts.push((start, k, job_idx));
+        }
+    }
+    starts
+}
+
+/// Remove `count` jobs chosen by `operator`, returns the schedule without them
+/// and the removed jobs
+fn destroy(
+    schedule: &Schedule,
+    jobs: &[Job],
+    machines: &[Machine],
+    operator: Destroy,
+    count: usize,
+    rng: &mut ChaCha8Rng,
+) -> (Schedule, Vec<usize>) {
+    let mut removed = vec![false; jobs.len()];
+    match operator {
+        Destroy::Random => {
+            for job_idx in rand::seq::index::sample(rng, jobs.len(), count) {
+                removed[job_idx] = true;
+            }
+        }
+        Destroy::Worst => {
+            let mut late: Vec<(f64, usize)> = Vec::with_capacity(jobs.len());
+            for (k, sequence) in schedule.sequences().iter().enumerate() {
+                for (i, &job_idx) in sequence.iter().enumerate() {
+                    let tardy =
+                        jobs[job_idx].tardy_work(&machines[k], schedule.completion_time(k, i));
+                    late.push((tardy, job_idx));
+                }
+            }
+            late.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+            for _ in 0..count {
+                let idx = (rng.random::<f64>().powi(WORST_BIAS) * late.len() as f64) as usize;
+                removed[late.remove(idx).1] = true;
+            }
+        }
+        Destroy::Segment => {
+            let mut starts = job_starts(schedule, jobs, machines);
+            starts.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.2.cmp(&b.2)));
+            let first = rng.random_range(0..=starts.len() - count);
+            for &(_, _, job_idx) in &starts[first..first + count] {
+                removed[job_idx] = true;
+            }
+        }
+        Destroy::Machine => {
+            let busy: Vec<usize> = (0..machines.len())
+                .filter(|&k| !schedule.sequence(k).is_empty())
+                .collect();
+            let sequence = schedule.sequence(busy[rng.random_range(0..busy.len())]);
+            let len = count.min(sequence.len());
+            let first = rng.random_range(0..=sequence.len() - len);
+            for &job_idx in &sequence[first..first + len] {
+                removed[job_idx] = true;
+            }
+        }
+    }
+
+    let sequences = schedule
+        .sequences()
+        .iter()
+        .map(|sequence| {
+            sequence
+                .iter()
+                .copied()
+                .filter(|&job_idx| !removed[job_idx])
+                .collect()
+        })
+        .collect();
+    let removed_jobs = (0..jobs.len())
+        .filter(|&job_idx| removed[job_idx])
+        .collect();
+    (Schedule::new(jobs, machines, sequences), removed_jobs)
+}
+
+/// Put the `removed` jobs back into `schedule` with `operator`
+fn repair(
+    schedule: &mut Schedule,
+    jobs: &[Job],
+    machines: &[Machine],
+    operator: Repair,
+    mut removed: Vec<usize>,
+    regret: usize,
+    rng: &mut ChaCha8Rng,
+) {
+    match operator {
+        Repair::Greedy => {
+            removed.shuffle(rng);
+            for job_idx in removed {
+                let (_, _, k, position) = schedule.best_insertion(jobs, machines, job_idx);
+                schedule.insert(jobs, machines, job_idx, k, position);
+            }
+        }
+        Repair::Regret => {
+            while !removed.is_empty() {
+                // (regret, best cost, index in removed, machine, position)
+                let mut chosen: Option<(f64, f64, usize, usize, usize)> = None;
+                for (idx, &job_idx) in removed.iter().enumerate() {
+                    let mut options: Vec<(f64, f64, usize, usize)> = (0..machines.len())
+                        .map(|k| {
+                            let (cost, finish, position) =
+                                schedule.best_position(jobs, machines, job_idx, k);
+                            (cost, finish, k, position)
+                        })
+                        .collect();
+                    options.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+                    let best = options[0];
+                    let job_regret: f64 = options
+                        .iter()
+                        .take(regret.max(2))
+                        .skip(1)
+                        .map(|option| option.0 - best.0)
+                        .sum();
+                    let better = chosen.is_none_or(|(chosen_regret, chosen_cost, ..)| {
+                        job_regret > chosen_regret
+                            || (job_regret == chosen_regret && best.0 < chosen_cost)
+                    });
+                    if better {
+                        chosen = Some((job_regret, best.0, idx, best.2, best.3));
+                    }
+                }
+                let (_, _, idx, k, position) = chosen.expect("Removed jobs are not empty");
+                let job_idx = removed.swap_remove(idx);
+                schedule.insert(jobs, machines, job_idx, k, position);
+            }
+        }
+        Repair::Dispatch => {
+            let rules = portfolio();
+            let rule = &rules[rng.random_range(0..rules.len())];
+            // highest priority first, as in run_simulation
+            let priority = |job_idx: usize| {
+                let job = &jobs[job_idx];
+                rule.calculate(job.r_j as f64, job, &machines[0])
+            };
+            removed.sort_by(|&a, &b| priority(b).total_cmp(&priority(a)).then(a.cmp(&b)));
+            for job_idx in removed {
+                let release = jobs[job_idx].r_j as f64;
+                let (k, position) = (0..machines.len())
+                    .map(|k| {
+                        let position = (0..schedule.sequence(k).len())
+                            .take_while(|&i| schedule.completion_time(k, i) <= release)
+                            .count();
+                        let (cost, finish) =
+                            schedule.insertion_delta(jobs, machines, job_idx, k, position);
+                        (cost, finish, k, position)
+                    })
+                    .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)))
+                    .map(|(_, _, k, position)| (k, position))
+                    .expect("Instance has no machines");
+                schedule.insert(jobs, machines, job_idx, k, position);
+            }
+        }
+    }
+}
+
+/// Adaptive large neighbourhood search starting from `start`.
+///
+/// Every iteration removes between `min_remove` and `max_remove` jobs with a
+/// destroy operator and reinserts them with a repair operator, both picked by
+/// roulette wheel. The new schedule is accepted by a simulated annealing
+/// criterion whose temperature cools with the time budget (or `iterations`).
+/// Operators earn scores for new best, improving and accepted schedules, and
+/// their weights follow the average score of every segment. The operator
+/// statistics are logged at the end.
+pub fn run_alns(
+    jobs: &[Job],
+    machines: &[Machine],
+    start: &ScheduleResult,
+    config: &AlnsConfig,
+    seed: u64,
+    bound: f64,
+    deadline: &Deadline,
+) -> ScheduleResult {
+    let rule_name = format!("{}+ALNS", start.rule_name);
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut current = Schedule::from_schedule(jobs, machines, &start.schedule);
+    let mut best = current.clone();
+    if jobs.is_empty() {
+        return best.to_result(jobs, machines, &rule_name);
+    }
+
+    let max_remove = config.max_remove.clamp(1, jobs.len());
+    let min_remove = config.min_remove.clamp(1, max_remove);
+    let mut destroy_stats: Vec<OperatorStats> = DESTROY
+        .iter()
+        .map(|op| {
+            OperatorStats::new(match op {
+                Destroy::Random => "random",
+                Destroy::Worst => "worst",
+                Destroy::Segment => "segment",
+                Destroy::Machine => "machine",
+            })
+        })
+        .collect();
+    let mut repair_stats: Vec<OperatorStats> = REPAIR
+        .iter()
+        .map(|op| {
+            OperatorStats::new(match op {
+                Repair::Greedy => "greedy",
+                Repair::Regret => "regret",
+                Repair::Dispatch => "dispatch",
+            })
+        })
+        .collect();
+
+    // a schedule start_worse worse than the start is accepted with probability 1/2
+    let initial = (config.start_worse * current.total_cost()).max(1.0) / 2f64.ln();
+    let time_budget = (deadline.remaining() != Duration::MAX).then(|| deadline.remaining());
+    let started = deadline.elapsed();
+    let mut iteration: u64 = 0;
+
+    while !deadline.expired() && best.total_cost() > bound + IMPROVEMENT_EPS {
+        let progress = match time_budget {
+            Some(budget) => (deadline.elapsed() - started).div_duration_f64(budget),
+            None => iteration as f64 / config.iterations as f64,
+        };
+        if progress >= 1.0 {
+            break;
+        }
+        let temperature = initial * FINAL_TEMPERATURE.powf(progress);
+        iteration += 1;
+
+        let d = select(&destroy_stats, &mut rng);
+        let r = select(&repair_stats, &mut rng);
+        let count = rng.random_range(min_remove..=max_remove);
+        let (mut candidate, removed) =
+            destroy(&current, jobs, machines, DESTROY[d], count, &mut rng);
+        repair(
+            &mut candidate,
+            jobs,
+            machines,
+            REPAIR[r],
+            removed,
+            config.regret,
+            &mut rng,
+        );
+
+        let delta = candidate.total_cost() - current.total_cost();
+        let score = if candidate.total_cost() < best.total_cost() - IMPROVEMENT_EPS {
+            SCORE_BEST
+        } else if delta < -IMPROVEMENT_EPS {
+            SCORE_IMPROVED
+        } else if delta <= 0.0 || rng.random::<f64>() < (-delta / temperature).exp() {
+            SCORE_ACCEPTED
+        } else {
+            0.0
+        };
+        if score > 0.0 {
+            current = candidate;
+            if score == SCORE_BEST {
+                best = current.clone();
+            }
+        }
+        destroy_stats[d].record(score);
+        repair_stats[r].record(score);
+
+        if iteration.is_multiple_of(config.segment.max(1)) {
+            for op in destroy_stats.iter_mut().chain(repair_stats.iter_mut()) {
+                op.update_weight(config.reaction);
+            }
+        }
+    }
+
+    debug!(
+        "ALNS: {} iterations, {} -> {}",
+        iteration,
+        start.total_tardy_work,
+        best.total_cost()
+    );
+    for op in destroy_stats.iter().chain(&repair_stats) {
+        op.log();
+    }
+    best.to_result(jobs, machines, &rule_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::algo::des::best_dispatch;
+    use crate::problem_2::testing::*;
+
+    #[test]
+    fn alns_improves_on_its_start_and_repeats() {
+        let config = AlnsConfig {
+            max_remove: 8,
+            iterations: 500,
+            ..AlnsConfig::default()
+        };
+        for seed in 0..10 {
+            let instance = random_instance(20, 3, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            let start = best_dispatch(jobs, machines, 0.0);
+            let run = || {
+                run_alns(
+                    jobs,
+                    machines,
+                    &start,
+                    &config,
+                    seed,
+                    0.0,
+                    &Deadline::unlimited(),
+                )
+            };
+            let result = run();
+            assert_consistent(jobs, machines, &result.schedule, result.total_tardy_work);
+            assert!(result.total_tardy_work <= start.total_tardy_work + TOLERANCE);
+            assert_eq!(assignment(&run().schedule), assignment(&result.schedule));
+        }
+    }
+}
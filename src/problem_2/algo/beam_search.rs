@@ -1,15 +1,183 @@
+use crate::deadline::Deadline;
 use crate::problem_2::models::*;
+use clap::{ArgAction, Args, ValueEnum};
+use log::{info, warn};
+use rayon::prelude::*;
 use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
 
-#[derive(Clone)]
+/// Upper bound of the beam width picked in adaptive mode
+pub const MAX_ADAPTIVE_BEAM_WIDTH: usize = 2000;
+/// Share of the estimated time budget the adaptive width plans to use
+const ADAPTIVE_SAFETY_FACTOR: f64 = 0.4;
+/// Number of root children expanded to measure the cost of an expansion
+const CALIBRATION_EXPANSIONS: usize = 8;
+/// Smallest improvement a swap must bring to replace a node in the recovering variant
+const RECOVERY_EPS: f64 = 1e-9;
+
+/// Order in which the pilot rollout dispatches the remaining jobs
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum PilotRule {
+    /// priority rule of the search (the portfolio entry)
+    #[default]
+    Portfolio,
+    /// input order of the jobs
+    Index,
+    /// earliest due date first
+    Edd,
+    /// earliest release date first
+    Release,
+    /// smallest due date slack (d_j - p_j) first
+    Slack,
+}
+
+/// How the pilot estimates the late work of the jobs a node has not scheduled
+#[derive(Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum PilotStrategy {
+    /// greedy rollout of all remaining jobs
+    #[default]
+    Full,
+    /// rollout of the next `--beam-pilot-horizon` jobs, lower bound for the rest
+    Truncated,
+    /// full rollout, cached on the node state (unscheduled jobs and machine
+    /// free times) so equal children are rolled out once; with the portfolio
+    /// pilot rule the rollout follows the rule ranked at the child itself
+    Memoized,
+}
+
+/// Variant of the beam search
+#[derive(Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum BeamVariant {
+    /// one pass with a fixed width
+    #[default]
+    Single,
+    /// passes of width 1, 2, 4, ... up to `--beam-width` until the time budget is spent
+    Widening,
+    /// one pass that repairs every kept node by swapping its last jobs
+    Recovering,
+}
+
+/// Parameters of the beam search
+#[derive(Args, Clone)]
+pub struct BeamSearchConfig {
+    /// Beam width (number of nodes kept per level); picked from the time
+    /// budget and instance size when not given. The largest width of the
+    /// widening variant
+    #[arg(long = "beam-width")]
+    pub width: Option<usize>,
+
+    /// Number of children expanded from every node
+    #[arg(long = "beam-branch-limit", default_value_t = 20)]
+    pub branch_limit: usize,
+
+    /// Rank children with a greedy pilot rollout (otherwise by late work so far)
+    #[arg(long = "beam-pilot", default_value_t = true, action = ArgAction::Set)]
+    pub pilot: bool,
+
+    /// Job order used by the pilot rollout
+    #[arg(long = "beam-pilot-rule", value_enum, default_value_t = PilotRule::Portfolio)]
+    pub pilot_rule: PilotRule,
+
+    /// How the pilot evaluates the unscheduled jobs
+    #[arg(long = "beam-pilot-strategy", value_enum, default_value_t = PilotStrategy::Full)]
+    pub pilot_strategy: PilotStrategy,
+
+    /// Jobs rolled out by the truncated pilot
+    #[arg(long = "beam-pilot-horizon", default_value_t = 50)]
+    pub pilot_horizon: usize,
+
+    /// Rollouts kept by the memoized pilot (the cache is cleared when full)
+    #[arg(long = "beam-pilot-cache", default_value_t = 200_000)]
+    pub pilot_cache: usize,
+
+    /// Variant of the search
+    #[arg(long = "beam-variant", value_enum, default_value_t = BeamVariant::Single)]
+    pub variant: BeamVariant,
+
+    /// Jobs at the end of a node's schedule whose pairwise swaps the
+    /// recovering variant tries
+    #[arg(long = "beam-recovery-window", default_value_t = 4)]
+    pub recovery_window: usize,
+
+    /// Drop nodes whose state duplicates or is dominated by another node of the level
+    #[arg(long = "beam-dominance", default_value_t = true, action = ArgAction::Set)]
+    pub dominance: bool,
+}
+
+impl Default for BeamSearchConfig {
+    fn default() -> Self {
+        BeamSearchConfig {
+            width: None,
+            branch_limit: 20,
+            pilot: true,
+            pilot_rule: PilotRule::Portfolio,
+            pilot_strategy: PilotStrategy::Full,
+            pilot_horizon: 50,
+            pilot_cache: 200_000,
+            variant: BeamVariant::Single,
+            recovery_window: 4,
+            dominance: true,
+        }
+    }
+}
+
+/// Set of job indices, one bit per job
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JobSet {
+    words: Vec<u64>,
+}
+
+impl JobSet {
+    /// Set of all jobs 0..num_jobs
+    pub fn full(num_jobs: usize) -> Self {
+        let mut words = vec![u64::MAX; num_jobs.div_ceil(64)];
+        if !num_jobs.is_multiple_of(64) {
+            if let Some(last) = words.last_mut() {
+                *last = (1 << (num_jobs % 64)) - 1;
+            }
+        }
+        JobSet { words }
+    }
+
+    #[inline]
+    pub fn contains(&self, idx: usize) -> bool {
+        self.words[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    #[inline]
+    pub fn remove(&mut self, idx: usize) {
+        self.words[idx / 64] &= !(1 << (idx % 64));
+    }
+
+    /// Jobs in increasing index order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(w, &word)| {
+            let mut rest = word;
+            std::iter::from_fn(move || {
+                if rest == 0 {
+                    return None;
+                }
+                let bit = rest.trailing_zeros() as usize;
+                rest &= rest - 1;
+                Some(w * 64 + bit)
+            })
+        })
+    }
+}
+
+/// Partial schedule of the beam. A node stores only the job appended to its
+/// parent; the full schedule is rebuilt from the parent chain when needed
 pub struct SearchNode {
-    // IDs of jobs scheduled so far
-    pub scheduled_jobs: Vec<usize>,
-    // reference to JobResults scheduled so far
-    pub scheduled_results: Vec<JobResult>,
-    // Remaining unscheduled jobs (bitmask or boolean vec is faster)
-    pub unscheduled_mask: Vec<bool>,
+    // Node this one extends, None for the root
+    pub parent: Option<Arc<SearchNode>>,
+    // Job appended to the parent, None for the root
+    pub last: Option<JobResult>,
+    // Number of jobs scheduled so far
+    pub depth: usize,
+    // Remaining unscheduled jobs
+    pub unscheduled: JobSet,
     // Current state of machines (when they become free)
     pub machine_finish_times: Vec<f64>,
     // The objective value incurred SO FAR
@@ -21,24 +189,172 @@ pub struct SearchNode {
 impl SearchNode {
     pub fn new(num_jobs: usize, num_machines: usize) -> Self {
         SearchNode {
-            scheduled_jobs: Vec::new(),
-            scheduled_results: Vec::new(),
-            unscheduled_mask: vec![true; num_jobs],
+            parent: None,
+            last: None,
+            depth: 0,
+            unscheduled: JobSet::full(num_jobs),
             machine_finish_times: vec![0.0; num_machines],
             current_tardy_work: 0.0,
             estimated_total_cost: 0.0,
         }
     }
 
+    /// Child of `parent` with `result` appended; the estimate is the late work
+    /// so far until the caller replaces it, before the child is ranked
+    pub fn child(parent: &Arc<SearchNode>, result: JobResult) -> Self {
+        let mut unscheduled = parent.unscheduled.clone();
+        unscheduled.remove(result.job_id);
+        let mut machine_finish_times = parent.machine_finish_times.clone();
+        machine_finish_times[result.machine_id] = result.completion_time;
+        let current_tardy_work = parent.current_tardy_work + result.tardy_work;
+        SearchNode {
+            parent: Some(Arc::clone(parent)),
+            last: Some(result),
+            depth: parent.depth + 1,
+            unscheduled,
+            machine_finish_times,
+            current_tardy_work,
+            estimated_total_cost: current_tardy_work,
+        }
+    }
+
+    /// Job results from the root to this node, in scheduling order
+    pub fn schedule(&self) -> Vec<JobResult> {
+        let mut results = Vec::with_capacity(self.depth);
+        let mut node = Some(self);
+        while let Some(current) = node {
+            results.extend(current.last.clone());
+            node = current.parent.as_deref();
+        }
+        results.reverse();
+        results
+    }
+
+    /// Machine free times with the times of every group of identical
+    /// machines sorted, equal for nodes that differ only by a permutation of
+    /// interchangeable machines
+    pub fn machine_state(&self, groups: &[Vec<usize>]) -> Vec<f64> {
+        machine_state(&self.machine_finish_times, groups)
+    }
+
     // Deterministic comparison for sorting
-    pub fn cmp(&self, other: &Self) -> Ordering {
+    pub fn cmp_cost(&self, other: &Self) -> Ordering {
         // Lower cost is better
         self.estimated_total_cost
             .total_cmp(&other.estimated_total_cost)
             .then_with(|| self.current_tardy_work.total_cmp(&other.current_tardy_work))
-            .then_with(|| self.scheduled_jobs.cmp(&other.scheduled_jobs))
+            .then_with(|| {
+                let last_job = |node: &Self| node.last.as_ref().map(|last| last.job_id);
+                last_job(self).cmp(&last_job(other))
+            })
+    }
+}
+
+impl Drop for SearchNode {
+    /// Unlink the parent chain iteratively, dropping a chain of n nodes
+    /// recursively could overflow the stack
+    fn drop(&mut self) {
+        let mut parent = self.parent.take();
+        while let Some(node) = parent {
+            parent = match Arc::try_unwrap(node) {
+                Ok(mut node) => node.parent.take(),
+                Err(_) => None,
+            };
+        }
     }
 }
+
+/// `machine_times` with the times of every group of identical machines sorted
+fn machine_state(machine_times: &[f64], groups: &[Vec<usize>]) -> Vec<f64> {
+    let mut state = Vec::with_capacity(machine_times.len());
+    for group in groups {
+        let start = state.len();
+        state.extend(group.iter().map(|&k| machine_times[k]));
+        state[start..].sort_by(f64::total_cmp);
+    }
+    state
+}
+
+/// Nodes dropped from the beam because another node reached the same or a better state
+#[derive(Default)]
+pub struct PruningStats {
+    /// same scheduled jobs, machine free times and late work
+    pub duplicates: usize,
+    /// same scheduled jobs, no machine free later and no more late work
+    pub dominated: usize,
+}
+
+/// Machine indices grouped by speed; machines of a group are interchangeable
+pub fn identical_machine_groups(machines: &[Machine]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (k, machine) in machines.iter().enumerate() {
+        match groups
+            .iter_mut()
+            .find(|group| machines[group[0]].b_k == machine.b_k)
+        {
+            Some(group) => group.push(k),
+            None => groups.push(vec![k]),
+        }
+    }
+    groups
+}
+
+/// Remove duplicate and dominated nodes from `candidates` (sorted best first).
+///
+/// Nodes are bucketed by the hashed set of unscheduled jobs. Within a bucket
+/// a node is dominated when another one has no machine free later (comparing
+/// `machine_state`) and no more late work, and a duplicate when both are
+/// equal; of two equal nodes the one ranked first stays.
+pub fn prune_dominated(
+    candidates: Vec<SearchNode>,
+    groups: &[Vec<usize>],
+    stats: &mut PruningStats,
+) -> Vec<SearchNode> {
+    let dominates = |a: &[f64], a_tardy: f64, b: &[f64], b_tardy: f64| {
+        a_tardy <= b_tardy && a.iter().zip(b).all(|(a, b)| a <= b)
+    };
+    // (machine state, late work, index) of the nodes kept so far
+    let mut buckets: HashMap<JobSet, Vec<(Vec<f64>, f64, usize)>> = HashMap::new();
+    let mut keep = vec![true; candidates.len()];
+
+    for (idx, node) in candidates.iter().enumerate() {
+        let state = node.machine_state(groups);
+        let tardy_work = node.current_tardy_work;
+        let bucket = buckets.entry(node.unscheduled.clone()).or_default();
+
+        if let Some((kept_state, kept_tardy_work, _)) =
+            bucket.iter().find(|(kept_state, kept_tardy_work, _)| {
+                dominates(kept_state, *kept_tardy_work, &state, tardy_work)
+            })
+        {
+            if *kept_state == state && *kept_tardy_work == tardy_work {
+                stats.duplicates += 1;
+            } else {
+                stats.dominated += 1;
+            }
+            keep[idx] = false;
+            continue;
+        }
+
+        // the pilot estimate may rank a dominating node below the ones it dominates
+        bucket.retain(|(kept_state, kept_tardy_work, kept_idx)| {
+            let dominated = dominates(&state, tardy_work, kept_state, *kept_tardy_work);
+            if dominated {
+                keep[*kept_idx] = false;
+                stats.dominated += 1;
+            }
+            !dominated
+        });
+        bucket.push((state, tardy_work, idx));
+    }
+
+    candidates
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(node, keep)| keep.then_some(node))
+        .collect()
+}
+
 // Funkcja pomocnicza: znajdź najlepszą maszynę dla zadania (Greedy)
 #[inline]
 pub fn find_best_machine_assignment(
@@ -50,9 +366,7 @@ pub fn find_best_machine_assignment(
     let mut best_finish = f64::MAX;
 
     for (m_idx, m_params) in machines.iter().enumerate() {
-        let t_free = machine_times[m_idx];
-        let start = (t_free as f64).max(job.r_j as f64);
-        let finish_time = start + job.p_j as f64 * m_params.b_k;
+        let finish_time = job.completion_time(m_params, machine_times[m_idx]);
 
         if finish_time < best_finish {
             best_finish = finish_time;
@@ -62,33 +376,725 @@ pub fn find_best_machine_assignment(
     (best_m_idx, best_finish)
 }
 
-pub fn run_pilot_simulation(parent_node: &SearchNode, jobs: &[Job], machines: &[Machine]) -> f64 {
+/// Job indices in the order the pilot dispatches them,
+/// `None` when the order comes from the priority rule of the search
+pub fn pilot_order(jobs: &[Job], rule: PilotRule) -> Option<Vec<usize>> {
+    let mut order: Vec<usize> = (0..jobs.len()).collect();
+    match rule {
+        PilotRule::Portfolio => return None,
+        PilotRule::Index => {}
+        PilotRule::Edd => order.sort_by_key(|&idx| (jobs[idx].d_j, idx)),
+        PilotRule::Release => order.sort_by_key(|&idx| (jobs[idx].r_j, idx)),
+        PilotRule::Slack => order.sort_by_key(|&idx| (jobs[idx].d_j - jobs[idx].p_j, idx)),
+    }
+    Some(order)
+}
+
+/// Unscheduled jobs of `node`, highest priority first, with `rule` evaluated
+/// on the machine that becomes free first (ties broken by job index)
+pub fn rule_order(
+    node: &SearchNode,
+    jobs: &[Job],
+    machines: &[Machine],
+    rule: &dyn PriorityRule,
+) -> Vec<usize> {
+    let (m_idx, t_free) = node
+        .machine_finish_times
+        .iter()
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(m_idx, &t_free)| (m_idx, t_free))
+        .expect("Instance has no machines");
+    let machine = &machines[m_idx];
+
+    let mut ranked: Vec<(f64, usize)> = node
+        .unscheduled
+        .iter()
+        .map(|idx| (rule.calculate(t_free, &jobs[idx], machine), idx))
+        .collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    ranked.into_iter().map(|(_, idx)| idx).collect()
+}
+
+/// Late work of the unscheduled jobs of `parent_node` dispatched greedily in
+/// `order`. Only the first `horizon` of them are rolled out; each remaining job
+/// adds the late work it would have alone on the fastest machine, started when
+/// the first machine of the rollout frees up (a lower bound)
+pub fn run_pilot_simulation(
+    parent_node: &SearchNode,
+    jobs: &[Job],
+    machines: &[Machine],
+    machine_rule: &dyn MachineSelectionRule,
+    order: &[usize],
+    horizon: usize,
+) -> f64 {
     // Klonujemy stan maszyn, aby nie psuć węzła
     let mut temp_machine_times = parent_node.machine_finish_times.clone();
 
     let mut future_cost: f64 = 0.0;
 
     // Zbieramy wskaźniki do niezaplanowanych zadań
-    let pending_jobs: Vec<&Job> = jobs
+    let pending_jobs: Vec<&Job> = order
         .iter()
-        .enumerate()
-        .filter(|(idx, _)| parent_node.unscheduled_mask[*idx])
-        .map(|(_, job)| job)
+        .filter(|&&idx| parent_node.unscheduled.contains(idx))
+        .map(|&idx| &jobs[idx])
         .collect();
+    let rolled_out = pending_jobs.len().min(horizon);
 
-    for job in pending_jobs {
-        let (best_m, finish_time) =
-            find_best_machine_assignment(job, &temp_machine_times, machines);
+    for (i, &job) in pending_jobs[..rolled_out].iter().enumerate() {
+        let next = pending_jobs.get(i + 1).copied();
+        let best_m = machine_rule.select(job, next, &temp_machine_times, machines);
+        let finish_time = job.completion_time(&machines[best_m], temp_machine_times[best_m]);
 
         // Aktualizacja stanu symulacji
         temp_machine_times[best_m] = finish_time;
 
         // Obliczenie kosztu Y_j
-        let tardiness = (finish_time - job.d_j as f64).max(0.0);
-        let tardy_work =
-            tardiness.min(job.p_j as f64 * machines[best_m].b_k) / machines[best_m].b_k;
-        future_cost += tardy_work;
+        future_cost += job.tardy_work(&machines[best_m], finish_time);
+    }
+
+    if rolled_out < pending_jobs.len() {
+        let fastest = machines
+            .iter()
+            .min_by(|a, b| a.b_k.total_cmp(&b.b_k))
+            .expect("Instance has no machines");
+        let free_time = temp_machine_times.iter().copied().fold(f64::MAX, f64::min);
+        for job in &pending_jobs[rolled_out..] {
+            future_cost += job.tardy_work(fastest, job.completion_time(fastest, free_time));
+        }
     }
 
     future_cost
 }
+
+/// Pilot rollouts by node state, shared by the levels of a search. The state
+/// is the set of unscheduled jobs and the `machine_state`: permuting identical
+/// machines does not change the late work of a rollout
+pub struct PilotCache {
+    capacity: usize,
+    machine_groups: Vec<Vec<usize>>,
+    estimates: HashMap<(JobSet, Vec<u64>), f64>,
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl PilotCache {
+    pub fn new(capacity: usize, machines: &[Machine]) -> Self {
+        PilotCache {
+            capacity,
+            machine_groups: identical_machine_groups(machines),
+            estimates: HashMap::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    fn key(&self, node: &SearchNode) -> (JobSet, Vec<u64>) {
+        let state = node.machine_state(&self.machine_groups);
+        let times = state.iter().map(|t| t.to_bits()).collect();
+        (node.unscheduled.clone(), times)
+    }
+}
+
+/// Set the estimated cost of every child, rolling the pilot out in the order
+/// paired with it. With `PilotStrategy::Memoized` the rollouts are looked up
+/// in `cache` first and children with equal states that are not cached yet
+/// are rolled out once. The order of such a rollout must follow from the
+/// state alone, so with `PilotRule::Portfolio` the unscheduled jobs of the
+/// child are ranked by `priority_rule` instead of taking the paired order
+pub fn evaluate_children(
+    children: &mut [(SearchNode, &[usize])],
+    jobs: &[Job],
+    machines: &[Machine],
+    priority_rule: &dyn PriorityRule,
+    machine_rule: &dyn MachineSelectionRule,
+    config: &BeamSearchConfig,
+    cache: &mut PilotCache,
+) {
+    if !config.pilot {
+        return;
+    }
+    let horizon = match config.pilot_strategy {
+        PilotStrategy::Truncated => config.pilot_horizon,
+        PilotStrategy::Full | PilotStrategy::Memoized => usize::MAX,
+    };
+    let rollout = |(child, order): &(SearchNode, &[usize])| {
+        run_pilot_simulation(child, jobs, machines, machine_rule, order, horizon)
+    };
+
+    if config.pilot_strategy != PilotStrategy::Memoized {
+        children.par_iter_mut().for_each(|entry| {
+            entry.0.estimated_total_cost = entry.0.current_tardy_work + rollout(entry);
+        });
+        return;
+    }
+
+    // (key, child indices) of the states to roll out
+    let mut pending: HashMap<(JobSet, Vec<u64>), Vec<usize>> = HashMap::new();
+    let mut missing: Vec<(JobSet, Vec<u64>)> = Vec::new();
+    for (idx, (child, _)) in children.iter_mut().enumerate() {
+        let key = cache.key(child);
+        if let Some(&estimate) = cache.estimates.get(&key) {
+            child.estimated_total_cost = child.current_tardy_work + estimate;
+            cache.hits += 1;
+            continue;
+        }
+        let equal = pending.entry(key).or_insert_with_key(|key| {
+            missing.push(key.clone());
+            Vec::new()
+        });
+        if !equal.is_empty() {
+            cache.hits += 1;
+        }
+        equal.push(idx);
+    }
+    cache.misses += missing.len();
+
+    let estimates: Vec<f64> = missing
+        .par_iter()
+        .map(|key| {
+            let (child, paired) = &children[pending[key][0]];
+            let ranked;
+            let order = match config.pilot_rule {
+                PilotRule::Portfolio => {
+                    ranked = rule_order(child, jobs, machines, priority_rule);
+                    &ranked
+                }
+                _ => *paired,
+            };
+            run_pilot_simulation(child, jobs, machines, machine_rule, order, horizon)
+        })
+        .collect();
+    if cache.estimates.len() + missing.len() > cache.capacity {
+        cache.estimates.clear();
+    }
+    for (key, estimate) in missing.into_iter().zip(estimates) {
+        for &idx in &pending[&key] {
+            let child = &mut children[idx].0;
+            child.estimated_total_cost = child.current_tardy_work + estimate;
+        }
+        cache.estimates.insert(key, estimate);
+    }
+}
+
+/// Complete a partial node greedily (same order as the pilot), recording job results.
+/// Used when the time budget runs out before the beam reaches the last level.
+pub fn complete_greedily(
+    node: &Arc<SearchNode>,
+    jobs: &[Job],
+    machines: &[Machine],
+    machine_rule: &dyn MachineSelectionRule,
+    order: &[usize],
+) -> Arc<SearchNode> {
+    let mut completed = Arc::clone(node);
+    let pending: Vec<usize> = order
+        .iter()
+        .copied()
+        .filter(|&idx| node.unscheduled.contains(idx))
+        .collect();
+
+    for (i, &job_idx) in pending.iter().enumerate() {
+        let job = &jobs[job_idx];
+        let next = pending.get(i + 1).map(|&idx| &jobs[idx]);
+        let result = assign(job, next, &completed, machines, machine_rule);
+        completed = Arc::new(SearchNode::child(&completed, result));
+    }
+
+    completed
+}
+
+/// Result of `job` placed after `node` on the machine picked by `machine_rule`
+pub fn assign(
+    job: &Job,
+    next: Option<&Job>,
+    node: &SearchNode,
+    machines: &[Machine],
+    machine_rule: &dyn MachineSelectionRule,
+) -> JobResult {
+    let best_m = machine_rule.select(job, next, &node.machine_finish_times, machines);
+    let finish_time = job.completion_time(&machines[best_m], node.machine_finish_times[best_m]);
+    JobResult {
+        job_id: job.id,
+        machine_id: best_m,
+        completion_time: finish_time,
+        tardy_work: job.tardy_work(&machines[best_m], finish_time),
+    }
+}
+
+/// Child of `parent` with job `job_idx` appended on the machine picked by
+/// `machine_rule`; the next job of `order` is the one expected after it.
+/// The pilot estimate is left to `evaluate_children`
+pub fn expand_node(
+    parent: &Arc<SearchNode>,
+    job_idx: usize,
+    jobs: &[Job],
+    machines: &[Machine],
+    machine_rule: &dyn MachineSelectionRule,
+    order: &[usize],
+) -> SearchNode {
+    // 1. Przypisz zadanie do maszyny wskazanej przez regułę (Deterministyczne Greedy)
+    let next = order
+        .iter()
+        .find(|&&idx| idx != job_idx && parent.unscheduled.contains(idx))
+        .map(|&idx| &jobs[idx]);
+    let result = assign(&jobs[job_idx], next, parent, machines, machine_rule);
+
+    // 2-3. Dziecko przechowuje tylko nowe zadanie i koszt tego kroku
+    SearchNode::child(parent, result)
+}
+
+/// Beam width that should let the search finish before `deadline`.
+///
+/// The cost of a single expansion (including its share of ranking the
+/// parent's jobs) is measured on children of the root; on average the
+/// pilot has half of the jobs left, and there are n levels of
+/// `width * branch_limit` expansions spread over the rayon threads shared
+/// by `concurrent_runs` searches.
+pub fn adaptive_beam_width(
+    jobs: &[Job],
+    machines: &[Machine],
+    priority_rule: &dyn PriorityRule,
+    machine_rule: &dyn MachineSelectionRule,
+    config: &BeamSearchConfig,
+    deadline: &Deadline,
+    concurrent_runs: usize,
+) -> usize {
+    let n = jobs.len();
+    if n == 0 {
+        return 1;
+    }
+    let static_order = pilot_order(jobs, config.pilot_rule);
+    let root = Arc::new(SearchNode::new(n, machines.len()));
+
+    let branching = config.branch_limit.clamp(1, n) as f64;
+    let samples = CALIBRATION_EXPANSIONS.min(n);
+
+    let start = Instant::now();
+    let ranked = rule_order(&root, jobs, machines, priority_rule);
+    let ranking_secs = start.elapsed().as_secs_f64();
+
+    let order = static_order.as_deref().unwrap_or(&ranked);
+    let start = Instant::now();
+    let mut children: Vec<(SearchNode, &[usize])> = ranked
+        .iter()
+        .take(samples)
+        .map(|&job_idx| {
+            let child = expand_node(&root, job_idx, jobs, machines, machine_rule, order);
+            (child, order)
+        })
+        .collect();
+    let mut cache = PilotCache::new(samples, machines);
+    evaluate_children(
+        &mut children,
+        jobs,
+        machines,
+        priority_rule,
+        machine_rule,
+        config,
+        &mut cache,
+    );
+    std::hint::black_box(children);
+    let expansion_secs = start.elapsed().as_secs_f64() / samples as f64 + ranking_secs / branching;
+
+    // with more runs than threads every run only gets a fraction of a thread
+    let threads = rayon::current_num_threads() as f64 / concurrent_runs.max(1) as f64;
+    let budget_secs = deadline.remaining().as_secs_f64().min(1e6) * ADAPTIVE_SAFETY_FACTOR;
+    let secs_per_unit_width = n as f64 * branching * expansion_secs / 2.0 / threads;
+
+    let width = (budget_secs / secs_per_unit_width.max(f64::MIN_POSITIVE)).floor();
+    (width as usize).clamp(1, MAX_ADAPTIVE_BEAM_WIDTH)
+}
+
+/// Level expansion shared by the beam search variants: ranking the jobs of
+/// every node with the priority rule, building `branch_limit` children per
+/// node, evaluating them with the pilot and dropping dominated states. The
+/// pilot cache lives as long as the expander, so repeated passes over the same
+/// instance reuse the rollouts of earlier ones
+pub struct BeamExpander<'a> {
+    jobs: &'a [Job],
+    machines: &'a [Machine],
+    priority_rule: &'a dyn PriorityRule,
+    machine_rule: &'a dyn MachineSelectionRule,
+    config: &'a BeamSearchConfig,
+    static_order: Option<Vec<usize>>,
+    machine_groups: Vec<Vec<usize>>,
+    pilot_cache: PilotCache,
+    pruning: PruningStats,
+    recovered: usize,
+}
+
+impl<'a> BeamExpander<'a> {
+    pub fn new(
+        jobs: &'a [Job],
+        machines: &'a [Machine],
+        priority_rule: &'a dyn PriorityRule,
+        machine_rule: &'a dyn MachineSelectionRule,
+        config: &'a BeamSearchConfig,
+    ) -> Self {
+        BeamExpander {
+            jobs,
+            machines,
+            priority_rule,
+            machine_rule,
+            config,
+            static_order: pilot_order(jobs, config.pilot_rule),
+            machine_groups: identical_machine_groups(machines),
+            pilot_cache: PilotCache::new(config.pilot_cache, machines),
+            pruning: PruningStats::default(),
+            recovered: 0,
+        }
+    }
+
+    /// Children of all nodes of `beam`, best first, without duplicate or
+    /// dominated states (when enabled)
+    pub fn expand(&mut self, beam: &[Arc<SearchNode>]) -> Vec<SearchNode> {
+        let (jobs_ref, machines_ref) = (self.jobs, self.machines);
+        let (priority_rule, machine_rule) = (self.priority_rule, self.machine_rule);
+        let branch_limit = self.config.branch_limit.max(1);
+        let static_order = self.static_order.as_deref();
+
+        // A. Generowanie kandydatów (Równolegle)
+        // Znajdź 'branch_limit' najlepszych kandydatów na następny ruch dla każdego rodzica.
+        // Zamiast sprawdzać wszystkie N zadań, sprawdzamy te o najwyższym priorytecie
+        // według reguły z portfela (Heuristic Filter).
+        let rankings: Vec<Vec<usize>> = beam
+            .par_iter()
+            .map(|parent| rule_order(parent, jobs_ref, machines_ref, priority_rule))
+            .collect();
+
+        // flat_map generuje listę wszystkich dzieci ze wszystkich węzłów w beamie
+        let mut children: Vec<(SearchNode, &[usize])> = beam
+            .par_iter()
+            .zip(&rankings)
+            .flat_map(|(parent, ranked)| {
+                // Pilot przechodzi zadania w tej samej kolejności (o ile nie wybrano stałej)
+                let order = static_order.unwrap_or(ranked);
+
+                // Dla każdego z Top K stwórz nowy węzeł
+                ranked
+                    .iter()
+                    .take(branch_limit)
+                    .map(|&job_idx| {
+                        let child = expand_node(
+                            parent,
+                            job_idx,
+                            jobs_ref,
+                            machines_ref,
+                            machine_rule,
+                            order,
+                        );
+                        (child, order)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        // PILOT: Oszacuj resztę (kosztowna operacja)
+        evaluate_children(
+            &mut children,
+            jobs_ref,
+            machines_ref,
+            priority_rule,
+            machine_rule,
+            self.config,
+            &mut self.pilot_cache,
+        );
+        let mut next_candidates: Vec<SearchNode> =
+            children.into_iter().map(|(child, _)| child).collect();
+
+        // B. Selekcja (Pruning) - Sekwencyjnie dla determinizmu
+        // Sortujemy wszystkich kandydatów
+        next_candidates.sort_by(|a, b| a.cmp_cost(b));
+
+        // Odrzucamy duplikaty i stany zdominowane, żeby nie zajmowały miejsc w wiązce
+        if self.config.dominance {
+            next_candidates =
+                prune_dominated(next_candidates, &self.machine_groups, &mut self.pruning);
+        }
+        next_candidates
+    }
+
+    /// `node` completed greedily in the pilot order
+    pub fn complete(&self, node: &Arc<SearchNode>) -> Arc<SearchNode> {
+        let ranked = rule_order(node, self.jobs, self.machines, self.priority_rule);
+        let order = self.static_order.as_deref().unwrap_or(&ranked);
+        complete_greedily(node, self.jobs, self.machines, self.machine_rule, order)
+    }
+
+    /// Swap repair of the last `recovery_window` jobs of `node`: every pair of
+    /// them is swapped and the jobs are dispatched again from the node that
+    /// precedes them. Returns the best rebuilt node that dominates `node`
+    /// (no more late work, no machine free later, better in one of them)
+    pub fn recover(&self, node: &Arc<SearchNode>) -> Option<Arc<SearchNode>> {
+        let window = self.config.recovery_window.min(node.depth);
+        if window < 2 {
+            return None;
+        }
+        let mut suffix = Vec::with_capacity(window);
+        let mut ancestor = Arc::clone(node);
+        for _ in 0..window {
+            suffix.push(ancestor.last.as_ref()?.job_id);
+            ancestor = Arc::clone(ancestor.parent.as_ref()?);
+        }
+        suffix.reverse();
+
+        let state = node.machine_state(&self.machine_groups);
+        let state_sum: f64 = state.iter().sum();
+        // (late work, summed free times, job order)
+        let mut best: Option<(f64, f64, Vec<usize>)> = None;
+        for i in 0..window - 1 {
+            for j in i + 1..window {
+                let mut order = suffix.clone();
+                order.swap(i, j);
+                let mut times = ancestor.machine_finish_times.clone();
+                let mut tardy_work = ancestor.current_tardy_work;
+                for (position, &job_idx) in order.iter().enumerate() {
+                    let job = &self.jobs[job_idx];
+                    let next = order.get(position + 1).map(|&idx| &self.jobs[idx]);
+                    let k = self.machine_rule.select(job, next, &times, self.machines);
+                    times[k] = job.completion_time(&self.machines[k], times[k]);
+                    tardy_work += job.tardy_work(&self.machines[k], times[k]);
+                }
+
+                let swapped_state = machine_state(&times, &self.machine_groups);
+                let swapped_sum: f64 = swapped_state.iter().sum();
+                let dominates = tardy_work <= node.current_tardy_work
+                    && swapped_state.iter().zip(&state).all(|(a, b)| a <= b)
+                    && (tardy_work < node.current_tardy_work - RECOVERY_EPS
+                        || swapped_sum < state_sum - RECOVERY_EPS);
+                let better = best.as_ref().is_none_or(|(best_tardy_work, best_sum, _)| {
+                    (tardy_work, swapped_sum) < (*best_tardy_work, *best_sum)
+                });
+                if dominates && better {
+                    best = Some((tardy_work, swapped_sum, order));
+                }
+            }
+        }
+
+        let (_, _, order) = best?;
+        let mut recovered = ancestor;
+        for (position, &job_idx) in order.iter().enumerate() {
+            let next = order.get(position + 1).map(|&idx| &self.jobs[idx]);
+            let result = assign(
+                &self.jobs[job_idx],
+                next,
+                &recovered,
+                self.machines,
+                self.machine_rule,
+            );
+            recovered = Arc::new(SearchNode::child(&recovered, result));
+        }
+        Some(recovered)
+    }
+
+    /// One pass of beam search with `width` nodes per level. Returns the best
+    /// complete node and whether any level had more candidates than `width`
+    /// (otherwise a wider pass would explore the same nodes). When `deadline`
+    /// expires the best node of the current beam is completed greedily
+    pub fn search(&mut self, width: usize, deadline: &Deadline) -> (Arc<SearchNode>, bool) {
+        let n = self.jobs.len();
+        let width = width.max(1);
+        let mut truncated = false;
+
+        // 1. Inicjalizacja
+        let root = Arc::new(SearchNode::new(n, self.machines.len()));
+        let mut beam = vec![root];
+
+        // 2. Pętla po kolejnych poziomach drzewa (dodajemy jedno zadanie na poziom)
+        for level in 0..n {
+            if deadline.expired() {
+                warn!(
+                    "{}: beam search stopped at level {}/{}, completing greedily",
+                    self.priority_rule.name(),
+                    level,
+                    n
+                );
+                return (self.complete(&beam[0]), true);
+            }
+
+            let mut next_candidates = self.expand(&beam);
+
+            // Zatrzymujemy tylko 'beam_width' najlepszych
+            if next_candidates.len() > width {
+                next_candidates.truncate(width);
+                truncated = true;
+            }
+
+            beam = next_candidates.into_iter().map(Arc::new).collect();
+
+            // Naprawa węzłów zamianami ostatnich zadań (recovering beam search)
+            if self.config.variant == BeamVariant::Recovering {
+                let recovered: Vec<Option<Arc<SearchNode>>> =
+                    beam.par_iter().map(|node| self.recover(node)).collect();
+                for (node, recovered) in beam.iter_mut().zip(recovered) {
+                    if let Some(recovered) = recovered {
+                        *node = recovered;
+                        self.recovered += 1;
+                    }
+                }
+            }
+        }
+
+        // 3. Zwracamy najlepszy wynik z ostatniej warstwy
+        let best = beam
+            .into_iter()
+            .min_by(|a, b| a.cmp_cost(b))
+            .expect("Beam is empty");
+        (best, truncated)
+    }
+
+    /// Log the pruning, pilot cache and recovery counters
+    pub fn log_stats(&self) {
+        info!(
+            "{}: pruned {} duplicate and {} dominated nodes, pilot cache {} hits, {} misses, {} nodes recovered",
+            self.priority_rule.name(),
+            self.pruning.duplicates,
+            self.pruning.dominated,
+            self.pilot_cache.hits,
+            self.pilot_cache.misses,
+            self.recovered
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::algo::portfolio::{portfolio, EarliestStart};
+    use crate::problem_2::testing::*;
+
+    #[test]
+    fn memoized_pilot_depends_only_on_the_state() {
+        let config = BeamSearchConfig {
+            pilot_strategy: PilotStrategy::Memoized,
+            ..BeamSearchConfig::default()
+        };
+        for seed in 0..10 {
+            let instance = random_instance(8, 2, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            let root = Arc::new(SearchNode::new(jobs.len(), machines.len()));
+            let forward: Vec<usize> = (0..jobs.len()).collect();
+            let backward: Vec<usize> = forward.iter().rev().copied().collect();
+            for rule in portfolio() {
+                let child = || expand_node(&root, 0, jobs, machines, &EarliestStart {}, &forward);
+                let ranked = rule_order(&child(), jobs, machines, rule.as_ref());
+                let expected = child().current_tardy_work
+                    + run_pilot_simulation(
+                        &child(),
+                        jobs,
+                        machines,
+                        &EarliestStart {},
+                        &ranked,
+                        usize::MAX,
+                    );
+                // equal children paired with the orders of different parents
+                for orders in [[&forward, &backward], [&backward, &forward]] {
+                    let mut children: Vec<(SearchNode, &[usize])> = orders
+                        .iter()
+                        .map(|order| (child(), order.as_slice()))
+                        .collect();
+                    let mut cache = PilotCache::new(config.pilot_cache, machines);
+                    evaluate_children(
+                        &mut children,
+                        jobs,
+                        machines,
+                        rule.as_ref(),
+                        &EarliestStart {},
+                        &config,
+                        &mut cache,
+                    );
+                    for (child, _) in &children {
+                        assert!(
+                            (child.estimated_total_cost - expected).abs() < TOLERANCE,
+                            "seed {} {}",
+                            seed,
+                            rule.name()
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Child of `root` with job `job_id` on machine `machine_id`
+    fn scheduled(
+        root: &Arc<SearchNode>,
+        job_id: usize,
+        machine_id: usize,
+        completion_time: f64,
+        tardy_work: f64,
+    ) -> SearchNode {
+        SearchNode::child(
+            root,
+            JobResult {
+                job_id,
+                machine_id,
+                completion_time,
+                tardy_work,
+            },
+        )
+    }
+
+    /// (job, free times, late work) of the nodes, to compare them
+    fn summary(nodes: &[SearchNode]) -> Vec<(Option<usize>, Vec<f64>, f64)> {
+        nodes
+            .iter()
+            .map(|node| {
+                let last = node.last.as_ref().map(|last| last.job_id);
+                (
+                    last,
+                    node.machine_finish_times.clone(),
+                    node.current_tardy_work,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn prune_dominated_removes_only_dominated_nodes() {
+        let root = Arc::new(SearchNode::new(3, 2));
+        let groups = [vec![0], vec![1]];
+        let node = |idx: usize| match idx {
+            0 => scheduled(&root, 0, 0, 3.0, 0.0),
+            // same jobs, the machine free later and more late work than node 0
+            1 => scheduled(&root, 0, 0, 5.0, 1.0),
+            // same jobs, freeing the other machine instead: incomparable
+            2 => scheduled(&root, 0, 1, 2.0, 0.0),
+            // a machine free earlier does not make up for more late work
+            3 => scheduled(&root, 0, 0, 2.0, 1.0),
+            // a different job set is never compared
+            _ => scheduled(&root, 1, 0, 10.0, 5.0),
+        };
+        let mut expected = summary(&[0, 2, 3, 4].map(node));
+        expected.sort_by(|a, b| a.partial_cmp(b).expect("NaN in a node"));
+
+        // the dominated node ranked before or after the one dominating it
+        for order in [[0, 2, 1, 3, 4], [1, 0, 2, 3, 4]] {
+            let mut stats = PruningStats::default();
+            let kept = prune_dominated(order.map(node).into(), &groups, &mut stats);
+            let mut kept = summary(&kept);
+            kept.sort_by(|a, b| a.partial_cmp(b).expect("NaN in a node"));
+            assert_eq!(kept, expected);
+            assert_eq!((stats.duplicates, stats.dominated), (0, 1));
+        }
+
+        // of two equal nodes the first one stays
+        let mut stats = PruningStats::default();
+        let kept = prune_dominated([0, 0].map(node).into(), &groups, &mut stats);
+        assert_eq!(kept.len(), 1);
+        assert_eq!((stats.duplicates, stats.dominated), (1, 0));
+    }
+
+    #[test]
+    fn prune_dominated_compares_identical_machines_sorted() {
+        let root = Arc::new(SearchNode::new(3, 2));
+        let groups = [vec![0, 1]];
+        // free times [3, 0] and [0, 2] are [0, 3] and [0, 2] up to swapping the machines
+        let dominated = scheduled(&root, 0, 0, 3.0, 0.0);
+        let better = scheduled(&root, 0, 1, 2.0, 0.0);
+        let mut stats = PruningStats::default();
+        let kept = prune_dominated(vec![dominated, better], &groups, &mut stats);
+        assert_eq!(summary(&kept), vec![(Some(0), vec![0.0, 2.0], 0.0)]);
+        assert_eq!(stats.dominated, 1);
+    }
+}
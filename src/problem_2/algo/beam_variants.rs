@@ -0,0 +1,325 @@
+use crate::deadline::Deadline;
+use crate::problem_2::algo::beam_search::{BeamExpander, BeamSearchConfig, JobSet, SearchNode};
+use crate::problem_2::algo::branch_and_bound::{canonical_branches, job_bound, ExactResult};
+use crate::problem_2::algo::des::ScheduleResult;
+use crate::problem_2::models::*;
+use log::{debug, info};
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Tolerance used when comparing costs
+const EPS: f64 = 1e-9;
+/// Beam width of the beam-stack search when `--beam-width` is not given
+const BEAM_STACK_WIDTH: usize = 64;
+/// Expected duration of a widening pass relative to the previous one (twice
+/// the nodes, plus some margin)
+const WIDENING_GROWTH: f64 = 2.2;
+
+/// Anytime beam search: passes of width 1, 2, 4, ... up to `max_width`.
+///
+/// The passes share `expander`, so with the memoized pilot every pass reuses
+/// the rollouts of the narrower ones. Stops at the lower bound `bound`, at
+/// `max_width`, when a pass never had to cut a level (a wider one would
+/// explore the same nodes) or when the next pass is not expected to finish
+/// before the deadline; a pass cut short by the deadline would only be
+/// completed greedily. Returns the best complete node.
+pub fn run_iterative_widening(
+    expander: &mut BeamExpander,
+    max_width: usize,
+    bound: f64,
+    deadline: &Deadline,
+) -> Arc<SearchNode> {
+    let max_width = max_width.max(1);
+    let mut width = 1;
+    let mut passes = 0;
+    let mut best: Option<Arc<SearchNode>> = None;
+
+    loop {
+        let pass_start = Instant::now();
+        let (node, truncated) = expander.search(width, deadline);
+        let pass_time = pass_start.elapsed();
+        passes += 1;
+        if best
+            .as_ref()
+            .is_none_or(|best| node.current_tardy_work < best.current_tardy_work - EPS)
+        {
+            best = Some(node);
+        }
+        let best_cost = best
+            .as_ref()
+            .map_or(f64::MAX, |best| best.current_tardy_work);
+        let next_fits = deadline.remaining() > pass_time.mul_f64(WIDENING_GROWTH);
+        if !truncated || width >= max_width || !next_fits || best_cost <= bound + EPS {
+            break;
+        }
+        width = (width * 2).min(max_width);
+    }
+
+    let best = best.expect("Widening ran no pass");
+    debug!(
+        "Iterative widening: {} passes, last width {}, best {}",
+        passes, width, best.current_tardy_work
+    );
+    best
+}
+
+/// Position of a node in the order beam-stack search expands a layer:
+/// (f, unscheduled jobs, machine free times)
+type StackKey = (f64, JobSet, Vec<u64>);
+
+fn stack_key(node: &SearchNode) -> StackKey {
+    (
+        node.estimated_total_cost,
+        node.unscheduled.clone(),
+        node.machine_finish_times
+            .iter()
+            .map(|time| time.to_bits())
+            .collect(),
+    )
+}
+
+fn cmp_key(a: &StackKey, b: &StackKey) -> Ordering {
+    a.0.total_cmp(&b.0)
+        .then_with(|| a.1.cmp(&b.1))
+        .then_with(|| a.2.cmp(&b.2))
+}
+
+/// Nodes of a layer with keys in [low, high); `None` is unbounded
+struct Interval {
+    low: Option<StackKey>,
+    high: Option<StackKey>,
+}
+
+impl Interval {
+    fn full() -> Self {
+        Interval {
+            low: None,
+            high: None,
+        }
+    }
+
+    fn contains(&self, key: &StackKey) -> bool {
+        self.low
+            .as_ref()
+            .is_none_or(|low| cmp_key(key, low) != Ordering::Less)
+            && self
+                .high
+                .as_ref()
+                .is_none_or(|high| cmp_key(key, high) == Ordering::Less)
+    }
+}
+
+/// (start, machine) of the last job of `node`, `None` for the root
+fn last_start(node: &SearchNode, jobs: &[Job], machines: &[Machine]) -> Option<(f64, usize)> {
+    node.last.as_ref().map(|last| {
+        let duration = jobs[last.job_id].p_j as f64 * machines[last.machine_id].b_k;
+        (last.completion_time - duration, last.machine_id)
+    })
+}
+
+/// Children of `parent` on the canonical branches of the branch and bound,
+/// with f the late work so far plus `job_bound` of every remaining job.
+/// Only the branching and `job_bound` are shared with the branch and bound;
+/// the children are not built by `BeamExpander`, whose estimates are not
+/// admissible. f is set before a child leaves this function.
+fn stack_children(parent: &Arc<SearchNode>, jobs: &[Job], machines: &[Machine]) -> Vec<SearchNode> {
+    let last = last_start(parent, jobs, machines);
+    let branches = canonical_branches(
+        jobs,
+        machines,
+        &parent.machine_finish_times,
+        last,
+        parent.unscheduled.iter(),
+    );
+    branches
+        .into_iter()
+        .map(|(job_idx, k, start, completion_time, tardy_work)| {
+            let result = JobResult {
+                job_id: jobs[job_idx].id,
+                machine_id: k,
+                completion_time,
+                tardy_work,
+            };
+            let mut child = SearchNode::child(parent, result);
+            let remaining: f64 = child
+                .unscheduled
+                .iter()
+                .map(|idx| job_bound(&jobs[idx], machines, &child.machine_finish_times, start))
+                .sum();
+            child.estimated_total_cost = child.current_tardy_work + remaining;
+            child
+        })
+        .collect()
+}
+
+/// Beam-stack search (Zhou and Hansen) over Q|r_j|ΣY_j.
+///
+/// A beam search over layers of partial schedules, branching like the
+/// branch and bound on every unscheduled job and machine and ranking the
+/// children by the admissible f of `stack_children`. It shares only that
+/// branching and `job_bound` with the branch and bound and none of the
+/// expansion of `BeamExpander`. For every layer the beam
+/// stack keeps the interval of f (ties broken by the node state) the layer
+/// currently holds, at most `width` nodes. When a layer has no children
+/// below the incumbent, the search backtracks to the deepest layer that was
+/// cut and moves on to its next interval. Only nodes with f not below the
+/// incumbent are ever dropped, so once the stack empties the incumbent is
+/// optimal; the search also ends once it reaches `root_bound`, a lower bound
+/// of the instance. Starting from `upper_bound`, the best schedule found is
+/// returned with `optimal == false` when the deadline expires first.
+pub fn run_beam_stack(
+    jobs: &[Job],
+    machines: &[Machine],
+    config: &BeamSearchConfig,
+    upper_bound: &ScheduleResult,
+    root_bound: f64,
+    deadline: &Deadline,
+) -> ExactResult {
+    let n = jobs.len();
+    let width = config.width.unwrap_or(BEAM_STACK_WIDTH).max(1);
+
+    let mut best_cost = upper_bound.total_tardy_work;
+    let mut best: Option<Arc<SearchNode>> = None;
+    let mut nodes: u64 = 0;
+    // layers[l] holds nodes with l jobs, stack[l] the interval of their
+    // children currently in layers[l + 1]
+    let mut layers: Vec<Vec<Arc<SearchNode>>> =
+        vec![vec![Arc::new(SearchNode::new(n, machines.len()))]];
+    let mut stack: Vec<Interval> = vec![Interval::full()];
+    let mut aborted = false;
+
+    while !stack.is_empty() && best_cost > root_bound + EPS {
+        if deadline.expired() {
+            aborted = true;
+            break;
+        }
+        let depth = layers.len() - 1;
+
+        let interval = &stack[depth];
+        let mut layer: Vec<(StackKey, SearchNode)> = layers[depth]
+            .par_iter()
+            .flat_map(|parent| stack_children(parent, jobs, machines))
+            .filter(|child| child.estimated_total_cost < best_cost - EPS)
+            .map(|child| (stack_key(&child), child))
+            .filter(|(key, _)| interval.contains(key))
+            .collect();
+        nodes += layer.len() as u64;
+        layer.sort_by(|a, b| cmp_key(&a.0, &b.0));
+        if layer.len() > width {
+            stack[depth].high = Some(layer[width].0.clone());
+            layer.truncate(width);
+        }
+
+        let mut next_layer = Vec::with_capacity(layer.len());
+        for (_, child) in layer {
+            if child.depth < n {
+                next_layer.push(Arc::new(child));
+            } else if child.current_tardy_work < best_cost - EPS {
+                best_cost = child.current_tardy_work;
+                best = Some(Arc::new(child));
+            }
+        }
+        next_layer.retain(|node| node.estimated_total_cost < best_cost - EPS);
+
+        if !next_layer.is_empty() {
+            layers.push(next_layer);
+            stack.push(Interval::full());
+            continue;
+        }
+
+        // back to the deepest layer with children left beyond its interval
+        while let Some(interval) = stack.last_mut() {
+            match interval.high.take() {
+                Some(high) => {
+                    interval.low = Some(high);
+                    break;
+                }
+                None => {
+                    stack.pop();
+                    layers.pop();
+                }
+            }
+        }
+    }
+
+    let optimal = !aborted || best_cost <= root_bound + EPS;
+    info!(
+        "Beam-stack search: {} nodes, best {}, {}",
+        nodes,
+        best_cost,
+        if optimal { "optimal" } else { "not proven" }
+    );
+
+    let result = match &best {
+        Some(node) => ScheduleResult {
+            rule_name: "BeamStack".to_string(),
+            schedule: node.schedule(),
+            total_tardy_work: node.current_tardy_work,
+        },
+        None => upper_bound.clone(),
+    };
+    ExactResult {
+        result,
+        optimal,
+        nodes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::lower_bound::lower_bound;
+    use crate::problem_2::schedule::Schedule;
+    use crate::problem_2::testing::*;
+
+    /// Every job on machine 0 in index order
+    fn naive_result(jobs: &[Job], machines: &[Machine]) -> ScheduleResult {
+        let mut sequences = vec![Vec::new(); machines.len()];
+        sequences[0] = (0..jobs.len()).collect();
+        Schedule::new(jobs, machines, sequences).to_result(jobs, machines, "naive")
+    }
+
+    #[test]
+    fn beam_stack_matches_brute_force() {
+        for seed in 0..30 {
+            let n = 3 + seed as usize % 5;
+            let m = 1 + seed as usize % 3;
+            let instance = random_instance(n, m, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            let optimum = brute_force(jobs, machines);
+
+            // narrow beams backtrack, wide ones keep whole layers
+            for width in [1, 3, 64] {
+                let config = BeamSearchConfig {
+                    width: Some(width),
+                    ..BeamSearchConfig::default()
+                };
+                let exact = run_beam_stack(
+                    jobs,
+                    machines,
+                    &config,
+                    &naive_result(jobs, machines),
+                    lower_bound(jobs, machines).value,
+                    &Deadline::unlimited(),
+                );
+                assert!(exact.optimal, "seed {} width {}", seed, width);
+                assert!(
+                    (exact.result.total_tardy_work - optimum).abs() < TOLERANCE,
+                    "seed {} width {}: {} instead of {}",
+                    seed,
+                    width,
+                    exact.result.total_tardy_work,
+                    optimum
+                );
+                assert_consistent(
+                    jobs,
+                    machines,
+                    &exact.result.schedule,
+                    exact.result.total_tardy_work,
+                );
+            }
+        }
+    }
+}
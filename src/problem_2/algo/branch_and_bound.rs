@@ -0,0 +1,394 @@
+use crate::deadline::Deadline;
+use crate::problem_2::algo::des::ScheduleResult;
+use crate::problem_2::models::*;
+use log::{debug, info};
+
+/// Largest instance `--algorithm exact` is used for: instances of 12 jobs are
+/// proven optimal within the n/10 s budget, while 14 jobs take about 10 s and
+/// 15 jobs often do not finish within a minute
+pub const MAX_EXACT_JOBS: usize = 12;
+/// Hard limit of the search: the scheduled set is a u64 bitmask
+pub const MAX_BITMASK_JOBS: usize = u64::BITS as usize;
+/// Tolerance used when comparing start times and costs
+const EPS: f64 = 1e-9;
+/// How many nodes are explored between two deadline checks
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
+
+/// Job appended to a node: (job, machine, start, completion, late work)
+pub type Branch = (usize, usize, f64, f64, f64);
+
+/// Is appending at (`start`, `machine`) consistent with the canonical order,
+/// given the (start, machine) of the last appended job (`None` at the root)
+pub fn is_canonical(last: Option<(f64, usize)>, start: f64, machine: usize) -> bool {
+    last.is_none_or(|(last_start, last_machine)| {
+        start > last_start + EPS || (start >= last_start - EPS && machine >= last_machine)
+    })
+}
+
+/// Branches on the `candidates` of a node with machine free times `free` and
+/// last appended job `last` that keep the canonical order. Identical machines
+/// free at the same time are interchangeable, only the first one that may be
+/// used is kept
+pub fn canonical_branches(
+    jobs: &[Job],
+    machines: &[Machine],
+    free: &[f64],
+    last: Option<(f64, usize)>,
+    candidates: impl IntoIterator<Item = usize>,
+) -> Vec<Branch> {
+    let mut branches = Vec::new();
+    for job_idx in candidates {
+        let job = &jobs[job_idx];
+        for (k, machine) in machines.iter().enumerate() {
+            let start = free[k].max(job.r_j as f64);
+            if !is_canonical(last, start, k) {
+                continue;
+            }
+            let symmetric = (0..k).any(|other| {
+                machines[other].b_k == machine.b_k
+                    && free[other] == free[k]
+                    && is_canonical(last, start, other)
+            });
+            if symmetric {
+                continue;
+            }
+            let completion = start + job.p_j as f64 * machine.b_k;
+            let tardy_work = job.tardy_work(machine, completion);
+            branches.push((job_idx, k, start, completion, tardy_work));
+        }
+    }
+    branches
+}
+
+/// Late work of `job` started on its own as early as possible on the best
+/// machine, but not before `min_start` (the canonical order appends no job
+/// earlier than the last one); summed over the unscheduled jobs it bounds
+/// any completion of a node
+pub fn job_bound(job: &Job, machines: &[Machine], free: &[f64], min_start: f64) -> f64 {
+    machines
+        .iter()
+        .zip(free)
+        .map(|(machine, &free)| {
+            let start = free.max(job.r_j as f64).max(min_start - EPS);
+            job.tardy_work(machine, start + job.p_j as f64 * machine.b_k)
+        })
+        .fold(f64::MAX, f64::min)
+}
+
+/// Outcome of the exact solver
+pub struct ExactResult {
+    /// best schedule found
+    pub result: ScheduleResult,
+    /// true when the search finished, i.e. `result` is optimal
+    pub optimal: bool,
+    /// number of explored nodes
+    pub nodes: u64,
+}
+
+/// Depth-first branch and bound over Q|r_j|ΣY_j.
+///
+/// Jobs are appended to the end of a machine in nondecreasing order of
+/// (start time, machine index), so every semi-active schedule is built
+/// exactly once. Late work is regular, so an optimal schedule is semi-active.
+///
+/// A job that can no longer start before its due date is fully late
+/// wherever it goes; moving it to the very end only lets other jobs finish
+/// earlier, so such jobs are never branched on and get appended to the
+/// latest finishing machine once nothing else is left.
+struct BranchAndBound<'a> {
+    jobs: &'a [Job],
+    machines: &'a [Machine],
+    deadline: &'a Deadline,
+
+    /// machine free times of the current node
+    free: Vec<f64>,
+    /// (job, machine, start) appended so far
+    path: Vec<(usize, usize, f64)>,
+    /// bitmask of scheduled jobs
+    scheduled: u64,
+    /// late work of the scheduled jobs
+    cost: f64,
+
+    /// the search stops once `best_cost` reaches this bound
+    root_bound: f64,
+    best_cost: f64,
+    best_path: Option<Vec<(usize, usize, f64)>>,
+    nodes: u64,
+    aborted: bool,
+}
+
+impl BranchAndBound<'_> {
+    /// (start, machine) of the last appended job
+    fn last(&self) -> Option<(f64, usize)> {
+        self.path
+            .last()
+            .map(|&(_, machine, start)| (start, machine))
+    }
+
+    /// Earliest start of the next job on `machine`, given the canonical order
+    fn min_start(&self) -> f64 {
+        self.path.last().map_or(0.0, |&(_, _, start)| start)
+    }
+
+    /// Sum of `job_bound` over the unscheduled jobs.
+    /// Also returns the mask of jobs that cannot start before their due date.
+    fn lower_bound(&self) -> (f64, u64) {
+        let min_start = self.min_start();
+        let mut bound = 0.0;
+        let mut doomed = 0;
+        for (job_idx, job) in self.jobs.iter().enumerate() {
+            if self.scheduled & (1 << job_idx) != 0 {
+                continue;
+            }
+            let earliest_start = self
+                .free
+                .iter()
+                .map(|&free| free.max(job.r_j as f64).max(min_start - EPS))
+                .fold(f64::MAX, f64::min);
+            if earliest_start >= job.d_j as f64 {
+                doomed |= 1 << job_idx;
+                bound += job.p_j as f64;
+                continue;
+            }
+            bound += job_bound(job, self.machines, &self.free, min_start);
+        }
+        (bound, doomed)
+    }
+
+    /// Current path with all `doomed` jobs appended to the latest finishing machine
+    fn completed_path(&self, doomed: u64) -> Vec<(usize, usize, f64)> {
+        let mut path = self.path.clone();
+        let (k, mut free) = self
+            .free
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("Instance has no machines");
+        for (job_idx, job) in self.jobs.iter().enumerate() {
+            if doomed & (1 << job_idx) != 0 {
+                let start = free.max(job.r_j as f64);
+                free = start + job.p_j as f64 * self.machines[k].b_k;
+                path.push((job_idx, k, start));
+            }
+        }
+        path
+    }
+
+    /// Canonical branches of the current node, most promising first;
+    /// `doomed` jobs are skipped
+    fn children(&self, doomed: u64) -> Vec<Branch> {
+        let candidates =
+            (0..self.jobs.len()).filter(|&job_idx| (self.scheduled | doomed) & (1 << job_idx) == 0);
+        let mut children = canonical_branches(
+            self.jobs,
+            self.machines,
+            &self.free,
+            self.last(),
+            candidates,
+        );
+        children.sort_by(|a, b| {
+            a.4.total_cmp(&b.4)
+                .then_with(|| a.3.total_cmp(&b.3))
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        children
+    }
+
+    fn search(&mut self) {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(DEADLINE_CHECK_INTERVAL) && self.deadline.expired() {
+            self.aborted = true;
+        }
+        if self.aborted {
+            return;
+        }
+
+        let (bound, doomed) = self.lower_bound();
+        if self.cost + bound >= self.best_cost - EPS {
+            return;
+        }
+
+        let remaining = !self.scheduled & (u64::MAX >> (64 - self.jobs.len().max(1)));
+        if remaining & !doomed == 0 {
+            // only fully late jobs left, the bound is exact
+            debug!("Branch and bound: new best {}", self.cost + bound);
+            self.best_cost = self.cost + bound;
+            self.best_path = Some(self.completed_path(doomed));
+            return;
+        }
+
+        for (job_idx, k, start, completion, tardy_work) in self.children(doomed) {
+            if self.cost + tardy_work >= self.best_cost - EPS {
+                continue;
+            }
+            let previous_free = self.free[k];
+            self.free[k] = completion;
+            self.scheduled |= 1 << job_idx;
+            self.cost += tardy_work;
+            self.path.push((job_idx, k, start));
+
+            self.search();
+
+            self.path.pop();
+            self.cost -= tardy_work;
+            self.scheduled &= !(1 << job_idx);
+            self.free[k] = previous_free;
+
+            if self.aborted || self.best_cost <= self.root_bound + EPS {
+                return;
+            }
+        }
+    }
+}
+
+/// Schedule listing jobs in the order they were appended
+fn path_to_result(
+    jobs: &[Job],
+    machines: &[Machine],
+    path: &[(usize, usize, f64)],
+) -> ScheduleResult {
+    let mut schedule = Vec::with_capacity(path.len());
+    let mut total_tardy_work = 0.0;
+    for &(job_idx, k, start) in path {
+        let job = &jobs[job_idx];
+        let completion = start + job.p_j as f64 * machines[k].b_k;
+        let tardy_work = job.tardy_work(&machines[k], completion);
+        total_tardy_work += tardy_work;
+        schedule.push(JobResult {
+            job_id: job.id,
+            machine_id: machines[k].id,
+            completion_time: completion,
+            tardy_work,
+        });
+    }
+    ScheduleResult {
+        rule_name: "B&B".to_string(),
+        schedule,
+        total_tardy_work,
+    }
+}
+
+/// Exact solver for small instances (practical up to `MAX_EXACT_JOBS` jobs,
+/// at most `MAX_BITMASK_JOBS`).
+///
+/// `upper_bound` (e.g. a beam search result) is returned unchanged when the
+/// search proves nothing better exists. The search ends early once a schedule
+/// reaches `root_bound`, a lower bound of the instance. When `deadline`
+/// expires the best schedule found so far is returned with `optimal == false`.
+pub fn run_branch_and_bound(
+    jobs: &[Job],
+    machines: &[Machine],
+    upper_bound: &ScheduleResult,
+    root_bound: f64,
+    deadline: &Deadline,
+) -> ExactResult {
+    assert!(
+        jobs.len() <= MAX_BITMASK_JOBS,
+        "Branch and bound supports at most {} jobs",
+        MAX_BITMASK_JOBS
+    );
+
+    let mut search = BranchAndBound {
+        jobs,
+        machines,
+        deadline,
+        free: vec![0.0; machines.len()],
+        path: Vec::with_capacity(jobs.len()),
+        scheduled: 0,
+        cost: 0.0,
+        root_bound,
+        best_cost: upper_bound.total_tardy_work + EPS,
+        best_path: None,
+        nodes: 0,
+        aborted: false,
+    };
+    if upper_bound.total_tardy_work > root_bound + EPS {
+        search.search();
+    }
+
+    let optimal = !search.aborted || search.best_cost <= root_bound + EPS;
+    info!(
+        "Branch and bound: {} nodes, best {}, {}",
+        search.nodes,
+        search.best_cost,
+        if optimal { "optimal" } else { "not proven" }
+    );
+
+    let result = match &search.best_path {
+        Some(path) => path_to_result(jobs, machines, path),
+        None => upper_bound.clone(),
+    };
+
+    ExactResult {
+        result,
+        optimal,
+        nodes: search.nodes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::lower_bound::lower_bound;
+    use crate::problem_2::schedule::Schedule;
+    use crate::problem_2::testing::*;
+
+    /// Every job on machine 0 in index order
+    fn naive_result(jobs: &[Job], machines: &[Machine]) -> ScheduleResult {
+        let mut sequences = vec![Vec::new(); machines.len()];
+        sequences[0] = (0..jobs.len()).collect();
+        Schedule::new(jobs, machines, sequences).to_result(jobs, machines, "naive")
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        for seed in 0..40 {
+            let n = 3 + seed as usize % 5;
+            let m = 1 + seed as usize % 3;
+            let instance = random_instance(n, m, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            let optimum = brute_force(jobs, machines);
+
+            let exact = run_branch_and_bound(
+                jobs,
+                machines,
+                &naive_result(jobs, machines),
+                lower_bound(jobs, machines).value,
+                &Deadline::unlimited(),
+            );
+            assert!(exact.optimal, "seed {}", seed);
+            assert!(
+                (exact.result.total_tardy_work - optimum).abs() < TOLERANCE,
+                "seed {}: {} instead of {}",
+                seed,
+                exact.result.total_tardy_work,
+                optimum
+            );
+            assert_consistent(
+                jobs,
+                machines,
+                &exact.result.schedule,
+                exact.result.total_tardy_work,
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_optimal_upper_bound() {
+        let instance = random_instance(6, 2, 7);
+        let (jobs, machines) = (&instance.jobs, &instance.machines);
+        let bound = lower_bound(jobs, machines).value;
+        let first = run_branch_and_bound(
+            jobs,
+            machines,
+            &naive_result(jobs, machines),
+            bound,
+            &Deadline::unlimited(),
+        );
+        let second =
+            run_branch_and_bound(jobs, machines, &first.result, bound, &Deadline::unlimited());
+        assert!(second.optimal);
+        assert!((second.result.total_tardy_work - first.result.total_tardy_work).abs() < TOLERANCE);
+    }
+}
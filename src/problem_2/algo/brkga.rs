@@ -0,0 +1,249 @@
+use crate::deadline::Deadline;
+use crate::problem_2::algo::des::{run_simulation, ScheduleResult};
+use crate::problem_2::algo::portfolio::portfolio;
+use crate::problem_2::models::*;
+use clap::Args;
+use log::debug;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+use std::time::Duration;
+
+/// Minimal decrease of late work accepted as a new best
+const IMPROVEMENT_EPS: f64 = 1e-9;
+
+/// Parameters of the biased random-key genetic algorithm
+#[derive(Args, Clone)]
+pub struct BrkgaConfig {
+    /// Number of chromosomes
+    #[arg(
+        id = "brkga_population",
+        long = "brkga-population",
+        default_value_t = 100
+    )]
+    pub population: usize,
+
+    /// Share of the population kept as the elite
+    #[arg(long = "brkga-elite-fraction", default_value_t = 0.2)]
+    pub elite_fraction: f64,
+
+    /// Share of the population replaced by random mutants every generation
+    #[arg(long = "brkga-mutant-fraction", default_value_t = 0.15)]
+    pub mutant_fraction: f64,
+
+    /// Probability that an offspring inherits a key from its elite parent
+    #[arg(long = "brkga-elite-bias", default_value_t = 0.7)]
+    pub elite_bias: f64,
+
+    /// Number of generations when the time budget is unlimited
+    /// (otherwise the search runs until the deadline)
+    #[arg(
+        id = "brkga_generations",
+        long = "brkga-generations",
+        default_value_t = 100
+    )]
+    pub generations: usize,
+}
+
+impl Default for BrkgaConfig {
+    fn default() -> Self {
+        BrkgaConfig {
+            population: 100,
+            elite_fraction: 0.2,
+            mutant_fraction: 0.15,
+            elite_bias: 0.7,
+            generations: 100,
+        }
+    }
+}
+
+/// Priority rule reading the priority of every job from a random-key vector,
+/// among released jobs the one with the smallest key starts first
+pub struct RandomKeys {
+    keys: Vec<f64>,
+}
+
+impl PriorityRule for RandomKeys {
+    fn name(&self) -> &str {
+        "BRKGA"
+    }
+
+    fn calculate(&self, _t_current: f64, job: &Job, _machine: &Machine) -> f64 {
+        -self.keys[job.id]
+    }
+
+    fn is_static(&self) -> bool {
+        true
+    }
+}
+
+/// Random-key vector with its decoded schedule
+struct Chromosome {
+    keys: Vec<f64>,
+    result: ScheduleResult,
+}
+
+impl Chromosome {
+    /// Decode `keys` with the DES dispatcher (`run_simulation`)
+    fn decode(jobs: &[Job], machines: &[Machine], keys: Vec<f64>) -> Self {
+        let rule = RandomKeys { keys };
+        let result = run_simulation(jobs, machines, &rule);
+        Chromosome {
+            keys: rule.keys,
+            result,
+        }
+    }
+
+    fn fitness(&self) -> f64 {
+        self.result.total_tardy_work
+    }
+}
+
+/// Keys reproducing the dispatch order of `result`
+fn encode(result: &ScheduleResult, jobs: usize) -> Vec<f64> {
+    let mut keys = vec![0.0; jobs];
+    for (position, job_result) in result.schedule.iter().enumerate() {
+        keys[job_result.job_id] = position as f64 / jobs as f64;
+    }
+    keys
+}
+
+fn random_keys(jobs: usize, rng: &mut ChaCha8Rng) -> Vec<f64> {
+    (0..jobs).map(|_| rng.random::<f64>()).collect()
+}
+
+/// Parameterised uniform crossover: each key comes from `elite` with
+/// probability `elite_bias`, otherwise from `other`
+fn crossover(elite: &[f64], other: &[f64], elite_bias: f64, rng: &mut ChaCha8Rng) -> Vec<f64> {
+    elite
+        .iter()
+        .zip(other)
+        .map(|(&e, &o)| if rng.random_bool(elite_bias) { e } else { o })
+        .collect()
+}
+
+/// Biased random-key genetic algorithm decoded by the DES dispatcher.
+///
+/// A chromosome holds one key in [0, 1) per job; `run_simulation` with the
+/// `RandomKeys` rule turns it into a schedule. The initial population contains
+/// the dispatch orders of the portfolio rules, the rest is random. Each
+/// generation copies the elite, adds random mutants and fills the rest by
+/// crossing an elite parent with a non-elite one. Keys are drawn sequentially
+/// from a ChaCha RNG and only decoding runs in parallel, so a run is
+/// reproducible for a given `seed`.
+pub fn run_brkga(
+    jobs: &[Job],
+    machines: &[Machine],
+    config: &BrkgaConfig,
+    seed: u64,
+    bound: f64,
+    deadline: &Deadline,
+) -> ScheduleResult {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let n = jobs.len();
+    let size = config.population.max(2);
+    let elite = ((size as f64 * config.elite_fraction) as usize).clamp(1, size - 1);
+    let mutants = ((size as f64 * config.mutant_fraction) as usize).min(size - elite);
+    let elite_bias = config.elite_bias.clamp(0.0, 1.0);
+
+    let mut keys: Vec<Vec<f64>> = portfolio()
+        .iter()
+        .map(|rule| encode(&run_simulation(jobs, machines, rule.as_ref()), n))
+        .take(size)
+        .collect();
+    while keys.len() < size {
+        keys.push(random_keys(n, &mut rng));
+    }
+    let mut population: Vec<Chromosome> = keys
+        .into_par_iter()
+        .map(|keys| Chromosome::decode(jobs, machines, keys))
+        .collect();
+    population.sort_by(|a, b| a.fitness().total_cmp(&b.fitness()));
+
+    let unlimited = deadline.remaining() == Duration::MAX;
+    let mut generation = 0;
+    while !deadline.expired() && population[0].fitness() > bound + IMPROVEMENT_EPS {
+        if unlimited && generation >= config.generations {
+            break;
+        }
+        generation += 1;
+
+        let mut offspring: Vec<Vec<f64>> = Vec::with_capacity(size - elite);
+        for _ in 0..mutants {
+            offspring.push(random_keys(n, &mut rng));
+        }
+        while offspring.len() < size - elite {
+            let elite_parent = &population[rng.random_range(0..elite)];
+            let other_parent = &population[rng.random_range(elite..size)];
+            offspring.push(crossover(
+                &elite_parent.keys,
+                &other_parent.keys,
+                elite_bias,
+                &mut rng,
+            ));
+        }
+
+        let decoded: Vec<Chromosome> = offspring
+            .into_par_iter()
+            .map(|keys| Chromosome::decode(jobs, machines, keys))
+            .collect();
+        population.truncate(elite);
+        population.extend(decoded);
+        population.sort_by(|a, b| a.fitness().total_cmp(&b.fitness()));
+    }
+
+    debug!(
+        "BRKGA: {} generations, best {}",
+        generation,
+        population[0].fitness()
+    );
+    population.swap_remove(0).result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::testing::*;
+
+    #[test]
+    fn encoded_dispatch_decodes_to_the_same_schedule() {
+        for seed in 0..20 {
+            let instance = random_instance(20, 3, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            for rule in portfolio() {
+                let dispatched = run_simulation(jobs, machines, rule.as_ref());
+                let decoded = Chromosome::decode(jobs, machines, encode(&dispatched, jobs.len()));
+                assert_eq!(
+                    assignment(&decoded.result.schedule),
+                    assignment(&dispatched.schedule),
+                    "seed {} {}",
+                    seed,
+                    rule.name()
+                );
+                assert!((decoded.fitness() - dispatched.total_tardy_work).abs() < TOLERANCE);
+            }
+        }
+    }
+
+    #[test]
+    fn brkga_improves_on_the_portfolio_and_repeats() {
+        let config = BrkgaConfig {
+            population: 30,
+            generations: 20,
+            ..BrkgaConfig::default()
+        };
+        for seed in 0..10 {
+            let instance = random_instance(20, 3, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            let start = portfolio()
+                .iter()
+                .map(|rule| run_simulation(jobs, machines, rule.as_ref()).total_tardy_work)
+                .fold(f64::MAX, f64::min);
+            let run = || run_brkga(jobs, machines, &config, seed, 0.0, &Deadline::unlimited());
+            let result = run();
+            assert_consistent(jobs, machines, &result.schedule, result.total_tardy_work);
+            assert!(result.total_tardy_work <= start + TOLERANCE);
+            assert_eq!(assignment(&run().schedule), assignment(&result.schedule));
+        }
+    }
+}
@@ -1,202 +1,216 @@
+use crate::deadline::Deadline;
 use crate::problem_2::algo::beam_search::{
-    find_best_machine_assignment, run_pilot_simulation, SearchNode,
+    adaptive_beam_width, BeamExpander, BeamSearchConfig, BeamVariant, MAX_ADAPTIVE_BEAM_WIDTH,
 };
+use crate::problem_2::algo::beam_variants::run_iterative_widening;
+use crate::problem_2::algo::portfolio::{dispatch_portfolio, EarliestStart};
+use crate::problem_2::algo::release_queue::ReleaseQueue;
 use crate::problem_2::models::*;
-use rayon::prelude::*;
-use std::collections::BinaryHeap;
+use log::debug;
 
+#[derive(Clone)]
 pub struct ScheduleResult {
     pub rule_name: String,
     pub schedule: Vec<JobResult>,
     pub total_tardy_work: f64,
 }
 
+impl ScheduleResult {
+    pub fn to_solution(&self) -> Solution {
+        Solution {
+            strategy: self.rule_name.clone(),
+            score: self.total_tardy_work,
+            job_results: self.schedule.clone(),
+        }
+    }
+}
+
+/// Greedy list scheduling, fast enough to always finish and so used as the fallback
 pub fn run_simulation(
-    jobs: &Vec<Job>,
-    machines: &Vec<Machine>,
+    jobs: &[Job],
+    machines: &[Machine],
     priority_rule: &dyn PriorityRule,
+) -> ScheduleResult {
+    run_dispatcher(jobs, machines, priority_rule, &EarliestStart {}, 0.0)
+}
+
+/// Event-driven non-delay list scheduler.
+///
+/// Machine completions and job releases (jobs sorted by r_j) are the events.
+/// Whenever a machine frees up the released job of highest priority is
+/// dispatched, to the machine picked by `machine_rule`; with nothing released
+/// the machine idles until the next release. Jobs released within `lookahead`
+/// after that moment compete too, so a machine may wait for an urgent job.
+/// Released jobs wait in a `ReleaseQueue` per machine speed (one for static
+/// rules), so a step costs O(log n) per speed plus O(log n) for every change
+/// of ranking the rule reports through `PriorityRule::overtake_time`: O(n log n)
+/// in total for static rules and LS. Rules other than static ones may depend
+/// on the machine only through b_k.
+pub fn run_dispatcher(
+    jobs: &[Job],
+    machines: &[Machine],
+    priority_rule: &dyn PriorityRule,
+    machine_rule: &dyn MachineSelectionRule,
+    lookahead: f64,
 ) -> ScheduleResult {
     let num_jobs = jobs.len();
-    let mut machine_heap: BinaryHeap<MachineState> = BinaryHeap::new();
-    for machine in machines.iter() {
-        machine_heap.push(MachineState {
-            machine_id: machine.id,
-            free_time: 0.0,
-        });
+    let mut machine_times: Vec<f64> = vec![0.0; machines.len()];
+
+    let mut by_release: Vec<usize> = (0..num_jobs).collect();
+    by_release.sort_by_key(|&idx| (jobs[idx].r_j, idx));
+    let mut next_release = 0;
+
+    // one queue per machine speed, the first machine of the speed ranks its jobs
+    let mut speed_of: Vec<usize> = vec![0; machines.len()];
+    let mut queues: Vec<ReleaseQueue> = Vec::new();
+    let mut speeds: Vec<f64> = Vec::new();
+    for (k, machine) in machines.iter().enumerate() {
+        if priority_rule.is_static() && !queues.is_empty() {
+            continue;
+        }
+        match speeds.iter().position(|&b_k| b_k == machine.b_k) {
+            Some(speed) => speed_of[k] = speed,
+            None => {
+                speed_of[k] = speeds.len();
+                speeds.push(machine.b_k);
+                queues.push(ReleaseQueue::new(jobs, machine, priority_rule));
+            }
+        }
     }
 
-    let mut is_scheduled: Vec<bool> = vec![false; num_jobs];
     let mut schedule_results: Vec<JobResult> = Vec::with_capacity(num_jobs);
     let mut total_tardy_work: f64 = 0.0;
 
     for _ in 0..num_jobs {
-        let mut earliest_machine = machine_heap.pop().unwrap();
-        let t_free = earliest_machine.free_time;
-
-        // Krok (c-e): Znajdź najlepsze nieplanowane zadanie
-        let (best_job_idx, _) = jobs
-            .par_iter()
+        let (earliest_machine, &free_time) = machine_times
+            .iter()
             .enumerate()
-            .filter(|(idx, _)| !is_scheduled[*idx])
-            .min_by(|(_, job), (_, other_job)| {
-                priority_rule
-                    .calculate(t_free, job, &machines[earliest_machine.machine_id])
-                    .total_cmp(&priority_rule.calculate(
-                        t_free,
-                        other_job,
-                        &machines[earliest_machine.machine_id],
-                    ))
-            })
-            .expect("No unscheduled jobs available");
-
-        is_scheduled[best_job_idx] = true;
+            .min_by(|a, b| a.1.total_cmp(b.1))
+            .expect("Instance has no machines");
+
+        // Nic nie czeka: maszyna stoi do najbliższego zwolnienia zadania
+        let mut t_decision = free_time;
+        if queues[0].is_empty() {
+            t_decision = t_decision.max(jobs[by_release[next_release]].r_j as f64);
+        }
+        while next_release < num_jobs
+            && jobs[by_release[next_release]].r_j as f64 <= t_decision + lookahead
+        {
+            let job_idx = by_release[next_release];
+            next_release += 1;
+            for queue in &mut queues {
+                queue.push(job_idx);
+            }
+        }
+
+        // Krok (c-e): Znajdź zadanie o najwyższym priorytecie (i kolejne po nim)
+        let (best, next_job_idx) = queues[speed_of[earliest_machine]].best_two(t_decision);
+        let best_job_idx = best.expect("No released jobs available");
+        for queue in &mut queues {
+            queue.remove(best_job_idx);
+        }
         let best_job = &jobs[best_job_idx];
-        let machine_params = &machines[earliest_machine.machine_id];
+        let machine_id = machine_rule.select(
+            best_job,
+            next_job_idx.map(|job_idx| &jobs[job_idx]),
+            &machine_times,
+            machines,
+        );
+        let machine_params = &machines[machine_id];
 
         // Krok (f): Oblicz czasy
-        let t_start = t_free.max(best_job.r_j as f64);
-        // Użyj arytmetyki stałoprzecinkowej
-        let t_proc = best_job.p_j as f64 * machine_params.b_k;
-        let t_complete = t_start + t_proc;
+        let t_complete = best_job.completion_time(machine_params, machine_times[machine_id]);
 
         // Krok (g-i): Zaktualizuj stan
-        earliest_machine.free_time = t_complete;
-        machine_heap.push(earliest_machine);
+        machine_times[machine_id] = t_complete;
 
         // Oblicz Y_j
-        let tardiness = (t_complete - best_job.d_j as f64).max(0.0);
-        let tardy_work =
-            tardiness.min(best_job.p_j as f64 * machine_params.b_k) / machine_params.b_k;
+        let tardy_work = best_job.tardy_work(machine_params, t_complete);
         total_tardy_work += tardy_work;
 
         schedule_results.push(JobResult {
             job_id: best_job.id,
             machine_id: machine_params.id,
             completion_time: t_complete,
-            tardy_work: tardy_work,
+            tardy_work,
         });
     }
 
     ScheduleResult {
-        rule_name: priority_rule.name().to_string(),
+        rule_name: format!("{}/{}", priority_rule.name(), machine_rule.name()),
         total_tardy_work,
         schedule: schedule_results,
     }
 }
 
+/// Best greedy schedule of the dispatch portfolio (every priority rule with
+/// every machine selection rule), the start of local searches
+pub fn best_dispatch(jobs: &[Job], machines: &[Machine], lookahead: f64) -> ScheduleResult {
+    dispatch_portfolio()
+        .iter()
+        .map(|(rule, machine_rule)| {
+            run_dispatcher(
+                jobs,
+                machines,
+                rule.as_ref(),
+                machine_rule.as_ref(),
+                lookahead,
+            )
+        })
+        .min_by(|a, b| a.total_tardy_work.total_cmp(&b.total_tardy_work))
+        .expect("Empty portfolio")
+}
+
+/// Beam search with pilot; `priority_rule` picks the children of every node
+/// and (with `PilotRule::Portfolio`) the job order of the pilot rollout,
+/// `machine_rule` places the jobs of the children and of the pilot.
+/// `config.variant` selects a single pass, iterative widening (which stops at
+/// the lower bound `bound`) or the recovering pass; when `deadline` expires
+/// the best node of the current beam is completed greedily
 pub fn run_beam_search(
-    jobs: &Vec<Job>,
-    machines: &Vec<Machine>,
+    jobs: &[Job],
+    machines: &[Machine],
     priority_rule: &dyn PriorityRule,
+    machine_rule: &dyn MachineSelectionRule,
+    config: &BeamSearchConfig,
+    bound: f64,
+    deadline: &Deadline,
 ) -> ScheduleResult {
-    // Zwraca (koszt, kolejność zadań)
-
-    let n = jobs.len();
-    let m = machines.len();
-
-    let beam_width = n;
-    let branch_limit = 20;
-
-    // 1. Inicjalizacja
-    let root = SearchNode::new(n, m);
-    let mut beam = vec![root];
-
-    // Dane do Rayona (wrapowanie w Arc nie jest konieczne dla referencji, ale ułatwia borrow checker)
-    let jobs_ref = jobs.as_slice();
-    let machines_ref = machines.as_slice();
-
-    // 2. Pętla po kolejnych poziomach drzewa (dodajemy jedno zadanie na poziom)
-    for _level in 0..n {
-        // A. Generowanie kandydatów (Równolegle)
-        // flat_map generuje listę wszystkich dzieci ze wszystkich węzłów w beamie
-        let mut next_candidates: Vec<SearchNode> = beam
-            .par_iter()
-            .flat_map(|parent| {
-                // Znajdź 'branch_limit' najlepszych kandydatów na następny ruch dla tego rodzica.
-                // Zamiast sprawdzać wszystkie N zadań, sprawdzamy te z najmniejszym MDD względem obecnego czasu.
-                // To drastycznie przyspiesza algorytm (Heuristic Filter).
-
-                let mut candidates_indices: Vec<usize> = parent
-                    .unscheduled_mask
-                    .iter()
-                    .enumerate()
-                    .filter(|(_, &active)| active)
-                    .map(|(idx, _)| idx)
-                    .collect();
-
-                candidates_indices.sort_by(|&id_a, &id_b| {
-                    let j_a = &jobs_ref[id_a];
-                    let j_b = &jobs_ref[id_b];
-                    let duration_a = (j_a.p_j as f64 * machines_ref[0].b_k).max(0.1); // unikanie dzielenia przez zero
-                    let duration_b = (j_b.p_j as f64 * machines_ref[0].b_k).max(0.1);
-                    let mdd_a =
-                        (j_a.d_j as f64 - duration_a) - parent.machine_finish_times[0] as f64;
-                    let mdd_b =
-                        (j_b.d_j as f64 - duration_b) - parent.machine_finish_times[0] as f64;
-                    mdd_a.total_cmp(&mdd_b)
-                });
-
-                // Bierzemy tylko TOP K
-                let top_k_indices = candidates_indices.into_iter().take(branch_limit);
-
-                // Dla każdego z Top K stwórz nowy węzeł
-                top_k_indices
-                    .map(|job_idx| {
-                        let mut child = parent.clone();
-                        let job = &jobs_ref[job_idx];
-
-                        // 1. Przypisz zadanie do najlepszej maszyny (Deterministyczne Greedy)
-                        let (best_m, finish_time) = find_best_machine_assignment(
-                            job,
-                            &child.machine_finish_times,
-                            machines_ref,
-                        );
-
-                        // 2. Aktualizuj stan dziecka
-                        child.machine_finish_times[best_m] = finish_time;
-                        child.unscheduled_mask[job_idx] = false;
-                        child.scheduled_jobs.push(job.id); // Zakładamy, że Job ma pole id
-
-                        // 3. Oblicz koszt rzeczywisty tego kroku
-
-                        let tardiness = (finish_time - job.d_j as f64).max(0.0);
-                        let raw_work = tardiness.min(job.p_j as f64 * machines[best_m].b_k);
-                        let tardy_work = (raw_work) / machines[best_m].b_k;
-                        child.scheduled_results.push(JobResult {
-                            job_id: job.id,
-                            machine_id: best_m,
-                            completion_time: finish_time,
-                            tardy_work: tardy_work,
-                        });
-                        child.current_tardy_work += tardy_work;
-
-                        // 4. PILOT: Oszacuj resztę (kosztowna operacja)
-                        let future_estimate = run_pilot_simulation(&child, jobs_ref, machines_ref);
-                        child.estimated_total_cost = child.current_tardy_work + future_estimate;
-
-                        child
-                    })
-                    .collect::<Vec<_>>()
-            })
-            .collect();
-
-        // B. Selekcja (Pruning) - Sekwencyjnie dla determinizmu
-        // Sortujemy wszystkich kandydatów
-        next_candidates.sort_by(|a, b| a.cmp(b));
-
-        // Zatrzymujemy tylko 'beam_width' najlepszych
-        if next_candidates.len() > beam_width {
-            next_candidates.truncate(beam_width);
+    let mut expander = BeamExpander::new(jobs, machines, priority_rule, machine_rule, config);
+    let best_node = match config.variant {
+        BeamVariant::Widening => {
+            let max_width = config.width.unwrap_or(MAX_ADAPTIVE_BEAM_WIDTH);
+            run_iterative_widening(&mut expander, max_width, bound, deadline)
         }
+        BeamVariant::Single | BeamVariant::Recovering => {
+            let beam_width = config
+                .width
+                .unwrap_or_else(|| {
+                    adaptive_beam_width(
+                        jobs,
+                        machines,
+                        priority_rule,
+                        machine_rule,
+                        config,
+                        deadline,
+                        1,
+                    )
+                })
+                .max(1);
+            debug!(
+                "{}: beam width {}, branch limit {}",
+                priority_rule.name(),
+                beam_width,
+                config.branch_limit.max(1)
+            );
+            expander.search(beam_width, deadline).0
+        }
+    };
+    expander.log_stats();
 
-        beam = next_candidates;
-    }
-
-    // 3. Zwracamy najlepszy wynik z ostatniej warstwy
-    let best_node = &beam[0];
     ScheduleResult {
-        rule_name: priority_rule.name().to_string(),
-        schedule: best_node.scheduled_results.clone(),
+        rule_name: format!("{}/{}", priority_rule.name(), machine_rule.name()),
+        schedule: best_node.schedule(),
         total_tardy_work: best_node.current_tardy_work,
     }
 }
@@ -0,0 +1,105 @@
+use std::collections::VecDeque;
+
+/// Residual capacities below this are treated as saturated
+const FLOW_EPS: f64 = 1e-9;
+
+struct Arc {
+    to: usize,
+    capacity: f64,
+}
+
+/// Dinic's maximum flow with real capacities
+///
+/// Arcs are stored in pairs, arc `2i + 1` is the reverse of arc `2i`.
+pub struct FlowNetwork {
+    arcs: Vec<Arc>,
+    adjacency: Vec<Vec<usize>>,
+    /// capacity each arc was created with
+    initial: Vec<f64>,
+    level: Vec<u32>,
+    next_arc: Vec<usize>,
+}
+
+impl FlowNetwork {
+    pub fn new(nodes: usize) -> Self {
+        FlowNetwork {
+            arcs: Vec::new(),
+            adjacency: vec![Vec::new(); nodes],
+            initial: Vec::new(),
+            level: vec![0; nodes],
+            next_arc: vec![0; nodes],
+        }
+    }
+
+    /// Add an arc `from -> to`, returns its id for `flow`
+    pub fn add_arc(&mut self, from: usize, to: usize, capacity: f64) -> usize {
+        let id = self.arcs.len();
+        self.arcs.push(Arc { to, capacity });
+        self.arcs.push(Arc {
+            to: from,
+            capacity: 0.0,
+        });
+        self.initial.push(capacity);
+        self.initial.push(0.0);
+        self.adjacency[from].push(id);
+        self.adjacency[to].push(id + 1);
+        id
+    }
+
+    /// Flow on arc `id` after `max_flow`
+    pub fn flow(&self, id: usize) -> f64 {
+        self.initial[id] - self.arcs[id].capacity
+    }
+
+    fn build_levels(&mut self, source: usize, sink: usize) -> bool {
+        self.level.fill(u32::MAX);
+        self.level[source] = 0;
+        let mut queue = VecDeque::from([source]);
+        while let Some(node) = queue.pop_front() {
+            for &id in &self.adjacency[node] {
+                let arc = &self.arcs[id];
+                if arc.capacity > FLOW_EPS && self.level[arc.to] == u32::MAX {
+                    self.level[arc.to] = self.level[node] + 1;
+                    queue.push_back(arc.to);
+                }
+            }
+        }
+        self.level[sink] != u32::MAX
+    }
+
+    fn push(&mut self, node: usize, sink: usize, limit: f64) -> f64 {
+        if node == sink {
+            return limit;
+        }
+        while self.next_arc[node] < self.adjacency[node].len() {
+            let id = self.adjacency[node][self.next_arc[node]];
+            let (to, capacity) = (self.arcs[id].to, self.arcs[id].capacity);
+            if capacity > FLOW_EPS && self.level[to] == self.level[node] + 1 {
+                let pushed = self.push(to, sink, limit.min(capacity));
+                if pushed > FLOW_EPS {
+                    self.arcs[id].capacity -= pushed;
+                    self.arcs[id ^ 1].capacity += pushed;
+                    return pushed;
+                }
+            }
+            self.next_arc[node] += 1;
+        }
+        0.0
+    }
+
+    /// Maximum flow from `source` to `sink`
+    pub fn max_flow(&mut self, source: usize, sink: usize) -> f64 {
+        let mut total = 0.0;
+        while self.build_levels(source, sink) {
+            self.next_arc.fill(0);
+            loop {
+                let pushed = self.push(source, sink, f64::MAX);
+                if pushed <= FLOW_EPS {
+                    break;
+                }
+                total += pushed;
+            }
+        }
+        total
+    }
+}
@@ -0,0 +1,352 @@
+use crate::deadline::Deadline;
+use crate::problem_2::algo::beam_search::find_best_machine_assignment;
+use crate::problem_2::algo::des::{run_dispatcher, ScheduleResult};
+use crate::problem_2::algo::portfolio::dispatch_portfolio;
+use crate::problem_2::elite::ElitePool;
+use crate::problem_2::models::*;
+use crate::problem_2::schedule::{Neighbourhood, Schedule};
+use clap::Args;
+use log::debug;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+use std::time::Duration;
+
+/// Minimal decrease of late work accepted as a new best
+const IMPROVEMENT_EPS: f64 = 1e-9;
+
+/// Parameters of the genetic (memetic) algorithm
+#[derive(Args, Clone)]
+pub struct GaConfig {
+    /// Number of individuals
+    #[arg(id = "ga_population", long = "ga-population", default_value_t = 50)]
+    pub population: usize,
+
+    /// Best individuals copied unchanged into the next generation
+    #[arg(long = "ga-elite", default_value_t = 2)]
+    pub elite: usize,
+
+    /// Probability that two parents are recombined (otherwise the first is copied)
+    #[arg(long = "ga-crossover-rate", default_value_t = 0.9)]
+    pub crossover_rate: f64,
+
+    /// Probability that an offspring is mutated
+    #[arg(long = "ga-mutation-rate", default_value_t = 0.3)]
+    pub mutation_rate: f64,
+
+    /// Probability that an offspring is educated by local search (0 gives a plain GA)
+    #[arg(long = "ga-education-rate", default_value_t = 0.2)]
+    pub education_rate: f64,
+
+    /// Random relocate / swap moves tried during education
+    #[arg(long = "ga-education-moves", default_value_t = 500)]
+    pub education_moves: usize,
+
+    /// Number of generations when the time budget is unlimited
+    /// (otherwise the search runs until the deadline)
+    #[arg(id = "ga_generations", long = "ga-generations", default_value_t = 200)]
+    pub generations: usize,
+}
+
+impl Default for GaConfig {
+    fn default() -> Self {
+        GaConfig {
+            population: 50,
+            elite: 2,
+            crossover_rate: 0.9,
+            mutation_rate: 0.3,
+            education_rate: 0.2,
+            education_moves: 500,
+            generations: 200,
+        }
+    }
+}
+
+/// Job permutation with the schedule it decodes to
+#[derive(Clone)]
+struct Individual {
+    permutation: Vec<usize>,
+    schedule: Schedule,
+}
+
+impl Individual {
+    fn fitness(&self) -> f64 {
+        self.schedule.total_cost()
+    }
+}
+
+/// List scheduler: every job of `permutation` goes to the end of the machine
+/// that completes it first (as the beam search pilot does)
+pub fn decode_permutation(jobs: &[Job], machines: &[Machine], permutation: &[usize]) -> Schedule {
+    let mut sequences = vec![Vec::new(); machines.len()];
+    let mut machine_times = vec![0.0; machines.len()];
+    for &job_idx in permutation {
+        let (k, finish_time) =
+            find_best_machine_assignment(&jobs[job_idx], &machine_times, machines);
+        machine_times[k] = finish_time;
+        sequences[k].push(job_idx);
+    }
+    Schedule::new(jobs, machines, sequences)
+}
+
+/// Jobs ordered by start time in `schedule`, so that decoding roughly
+/// reproduces it (used to write educated schedules back into the chromosome)
+fn schedule_permutation(jobs: &[Job], schedule: &Schedule) -> Vec<usize> {
+    let mut starts: Vec<(f64, usize)> = Vec::with_capacity(jobs.len());
+    for (k, sequence) in schedule.sequences().iter().enumerate() {
+        let mut free: f64 = 0.0;
+        for (i, &job_idx) in sequence.iter().enumerate() {
+            starts.push((free.max(jobs[job_idx].r_j as f64), job_idx));
+            free = schedule.completion_time(k, i);
+        }
+    }
+    starts.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+    starts.into_iter().map(|(_, job_idx)| job_idx).collect()
+}
+
+/// Order crossover (OX): a slice of `first` keeps its positions, the rest
+/// is filled with the remaining jobs in the relative order of `second`
+fn order_crossover(first: &[usize], second: &[usize], rng: &mut ChaCha8Rng) -> Vec<usize> {
+    let n = first.len();
+    let a = rng.random_range(0..n);
+    let b = rng.random_range(a..n);
+    let mut taken = vec![false; n];
+    for &job_idx in &first[a..=b] {
+        taken[job_idx] = true;
+    }
+    let mut rest = second.iter().copied().filter(|&job_idx| !taken[job_idx]);
+    (0..n)
+        .map(|position| {
+            if (a..=b).contains(&position) {
+                first[position]
+            } else {
+                rest.next()
+                    .expect("Parents are not permutations of the same jobs")
+            }
+        })
+        .collect()
+}
+
+/// Move a random job to a random position, or swap two jobs
+fn mutate(permutation: &mut Vec<usize>, rng: &mut ChaCha8Rng) {
+    let n = permutation.len();
+    let i = rng.random_range(0..n);
+    let j = rng.random_range(0..n);
+    if rng.random_bool(0.5) {
+        permutation.swap(i, j);
+    } else {
+        let job_idx = permutation.remove(i);
+        permutation.insert(j, job_idx);
+    }
+}
+
+/// Local search on the decoded schedule: `moves` random relocations and
+/// swaps, each applied when it lowers late work
+fn educate(
+    schedule: &mut Schedule,
+    jobs: &[Job],
+    machines: &[Machine],
+    moves: usize,
+    rng: &mut ChaCha8Rng,
+) {
+    for _ in 0..moves {
+        let neighbourhood = if rng.random_bool(0.5) {
+            Neighbourhood::Relocate
+        } else {
+            Neighbourhood::Swap
+        };
+        if let Some(mv) = schedule.random_move(neighbourhood, rng) {
+            schedule.try_move(jobs, machines, mv);
+        }
+    }
+}
+
+/// Binary tournament on fitness
+fn tournament<'a>(population: &'a [Individual], rng: &mut ChaCha8Rng) -> &'a Individual {
+    let a = &population[rng.random_range(0..population.len())];
+    let b = &population[rng.random_range(0..population.len())];
+    if a.fitness() <= b.fitness() {
+        a
+    } else {
+        b
+    }
+}
+
+/// Offspring of two tournament winners, built with its own RNG stream
+fn offspring(
+    population: &[Individual],
+    jobs: &[Job],
+    machines: &[Machine],
+    config: &GaConfig,
+    seed: u64,
+) -> Individual {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let first = tournament(population, &mut rng);
+    let second = tournament(population, &mut rng);
+    let mut permutation = if rng.random_bool(config.crossover_rate.clamp(0.0, 1.0)) {
+        order_crossover(&first.permutation, &second.permutation, &mut rng)
+    } else {
+        first.permutation.clone()
+    };
+    if rng.random_bool(config.mutation_rate.clamp(0.0, 1.0)) {
+        mutate(&mut permutation, &mut rng);
+    }
+
+    let mut schedule = decode_permutation(jobs, machines, &permutation);
+    if rng.random_bool(config.education_rate.clamp(0.0, 1.0)) {
+        educate(
+            &mut schedule,
+            jobs,
+            machines,
+            config.education_moves,
+            &mut rng,
+        );
+        permutation = schedule_permutation(jobs, &schedule);
+    }
+    Individual {
+        permutation,
+        schedule,
+    }
+}
+
+/// Memetic algorithm over job permutations decoded by a list scheduler.
+///
+/// The initial population holds the elites of `pool` and the dispatch orders
+/// of the dispatch portfolio, filled up with random permutations. Every new best
+/// individual is pushed to `pool`. Every generation keeps the `elite`
+/// best individuals and breeds the rest from tournament winners with order
+/// crossover, mutation and (sometimes) local-search education. Offspring are
+/// decoded in parallel; each gets a seed drawn from the main RNG, so a run is
+/// reproducible for a given `seed` regardless of the thread count.
+pub fn run_genetic(
+    jobs: &[Job],
+    machines: &[Machine],
+    config: &GaConfig,
+    seed: u64,
+    pool: &ElitePool,
+    bound: f64,
+    deadline: &Deadline,
+) -> ScheduleResult {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let size = config.population.max(2);
+
+    let mut permutations: Vec<Vec<usize>> = pool
+        .solutions()
+        .iter()
+        .map(|elite| {
+            schedule_permutation(
+                jobs,
+                &Schedule::from_schedule(jobs, machines, &elite.job_results),
+            )
+        })
+        .chain(dispatch_portfolio().iter().map(|(rule, machine_rule)| {
+            run_dispatcher(jobs, machines, rule.as_ref(), machine_rule.as_ref(), 0.0)
+                .schedule
+                .iter()
+                .map(|result| result.job_id)
+                .collect()
+        }))
+        .take(size)
+        .collect();
+    while permutations.len() < size {
+        let mut permutation: Vec<usize> = (0..jobs.len()).collect();
+        permutation.shuffle(&mut rng);
+        permutations.push(permutation);
+    }
+    let mut population: Vec<Individual> = permutations
+        .into_par_iter()
+        .map(|permutation| Individual {
+            schedule: decode_permutation(jobs, machines, &permutation),
+            permutation,
+        })
+        .collect();
+    population.sort_by(|a, b| a.fitness().total_cmp(&b.fitness()));
+
+    let unlimited = deadline.remaining() == Duration::MAX;
+    let elite = config.elite.min(size - 1);
+    let mut generation = 0;
+    while !deadline.expired() && population[0].fitness() > bound + IMPROVEMENT_EPS {
+        if unlimited && generation >= config.generations {
+            break;
+        }
+        generation += 1;
+
+        let seeds: Vec<u64> = (elite..size).map(|_| rng.random()).collect();
+        let children: Vec<Individual> = seeds
+            .into_par_iter()
+            .map(|child_seed| offspring(&population, jobs, machines, config, child_seed))
+            .collect();
+
+        let previous_best = population[0].fitness();
+        population.truncate(elite);
+        population.extend(children);
+        population.sort_by(|a, b| a.fitness().total_cmp(&b.fitness()));
+        if population[0].fitness() < previous_best - IMPROVEMENT_EPS {
+            pool.push(
+                population[0]
+                    .schedule
+                    .to_result(jobs, machines, "GA")
+                    .to_solution(),
+            );
+        }
+    }
+
+    debug!(
+        "GA: {} generations, best {}",
+        generation,
+        population[0].fitness()
+    );
+    population[0].schedule.to_result(jobs, machines, "GA")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::elite::EliteConfig;
+    use crate::problem_2::testing::*;
+
+    #[test]
+    fn ga_improves_on_its_population_and_repeats() {
+        let config = GaConfig {
+            population: 20,
+            generations: 20,
+            education_moves: 100,
+            ..GaConfig::default()
+        };
+        for seed in 0..10 {
+            let instance = random_instance(20, 3, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            // best decoded dispatch order of the initial population
+            let start = dispatch_portfolio()
+                .iter()
+                .map(|(rule, machine_rule)| {
+                    let dispatched =
+                        run_dispatcher(jobs, machines, rule.as_ref(), machine_rule.as_ref(), 0.0);
+                    let order: Vec<usize> = dispatched
+                        .schedule
+                        .iter()
+                        .map(|result| result.job_id)
+                        .collect();
+                    decode_permutation(jobs, machines, &order).total_cost()
+                })
+                .fold(f64::MAX, f64::min);
+            let run = || {
+                let pool = ElitePool::new(&EliteConfig::default(), jobs.len());
+                run_genetic(
+                    jobs,
+                    machines,
+                    &config,
+                    seed,
+                    &pool,
+                    0.0,
+                    &Deadline::unlimited(),
+                )
+            };
+            let result = run();
+            assert_consistent(jobs, machines, &result.schedule, result.total_tardy_work);
+            assert!(result.total_tardy_work <= start + TOLERANCE);
+            assert_eq!(assignment(&run().schedule), assignment(&result.schedule));
+        }
+    }
+}
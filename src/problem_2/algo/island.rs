@@ -0,0 +1,296 @@
+use crate::deadline::Deadline;
+use crate::incumbent::Incumbent;
+use crate::problem_2::algo::des::{run_dispatcher, ScheduleResult};
+use crate::problem_2::algo::portfolio::dispatch_portfolio;
+use crate::problem_2::algo::sa::{run_sa, SaConfig};
+use crate::problem_2::algo::tabu::{run_tabu, TabuConfig};
+use crate::problem_2::elite::{EliteConfig, ElitePool};
+use crate::problem_2::models::*;
+use clap::Args;
+use log::debug;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+use std::time::{Duration, Instant};
+
+/// Minimal decrease of late work accepted as a new best
+const IMPROVEMENT_EPS: f64 = 1e-9;
+/// Largest share of the remaining budget one epoch may take; the epochs after
+/// a longer one get proportionally fewer iterations
+const MAX_EPOCH_SHARE: f64 = 0.25;
+
+/// Parameters of the island model
+#[derive(Args, Clone)]
+pub struct IslandConfig {
+    /// Number of islands (defaults to the number of rayon threads)
+    #[arg(long = "islands")]
+    pub islands: Option<usize>,
+
+    /// Simulated annealing moves per epoch on SA islands
+    #[arg(
+        id = "island_sa_iterations",
+        long = "island-sa-iterations",
+        default_value_t = 20_000
+    )]
+    pub sa_iterations: u64,
+
+    /// Tabu search iterations per epoch on tabu islands
+    #[arg(
+        id = "island_tabu_iterations",
+        long = "island-tabu-iterations",
+        default_value_t = 200
+    )]
+    pub tabu_iterations: u64,
+
+    /// Number of epochs when the time budget is unlimited
+    /// (otherwise the islands run until the deadline)
+    #[arg(id = "island_epochs", long = "island-epochs", default_value_t = 50)]
+    pub epochs: usize,
+}
+
+impl Default for IslandConfig {
+    fn default() -> Self {
+        IslandConfig {
+            islands: None,
+            sa_iterations: 20_000,
+            tabu_iterations: 200,
+            epochs: 50,
+        }
+    }
+}
+
+/// Metaheuristic run on an island
+#[derive(Clone, Copy)]
+enum Heuristic {
+    Sa,
+    Tabu,
+}
+
+impl Heuristic {
+    fn name(&self) -> &'static str {
+        match self {
+            Heuristic::Sa => "SA",
+            Heuristic::Tabu => "Tabu",
+        }
+    }
+}
+
+struct Island {
+    /// start rule and heuristic, e.g. "ATC+SA"
+    name: String,
+    heuristic: Heuristic,
+    /// schedule the next epoch starts from
+    current: ScheduleResult,
+    best: ScheduleResult,
+    /// private pool, so that tabu restarts do not depend on other threads
+    pool: ElitePool,
+}
+
+impl Island {
+    /// One epoch of a fixed number of iterations, cut short only by the
+    /// deadline, so that with an unlimited one it does not depend on timing
+    #[allow(clippy::too_many_arguments)]
+    fn epoch(
+        &mut self,
+        jobs: &[Job],
+        machines: &[Machine],
+        sa: &SaConfig,
+        tabu: &TabuConfig,
+        seed: u64,
+        bound: f64,
+        deadline: &Deadline,
+    ) {
+        self.current = match self.heuristic {
+            Heuristic::Sa => run_sa(
+                jobs,
+                machines,
+                &self.current,
+                sa,
+                seed,
+                &self.pool,
+                bound,
+                deadline,
+            ),
+            Heuristic::Tabu => run_tabu(
+                jobs,
+                machines,
+                &self.current,
+                tabu,
+                seed,
+                &self.pool,
+                bound,
+                deadline,
+            ),
+        };
+        self.current.rule_name = self.name.clone();
+        if self.current.total_tardy_work < self.best.total_tardy_work - IMPROVEMENT_EPS {
+            self.best = self.current.clone();
+        }
+    }
+}
+
+/// Island model over simulated annealing and tabu search.
+///
+/// Island i starts from the greedy schedule of dispatch portfolio pair i and
+/// runs SA (even islands) or tabu search (odd islands). The islands run in parallel
+/// in epochs of a fixed number of iterations; after every epoch each island
+/// receives the best schedule of its neighbour on a ring and continues from it
+/// when it is better than its own. The deadline also stops an epoch early;
+/// an epoch that took more than `MAX_EPOCH_SHARE` of the remaining budget
+/// shortens the following ones. Seeds are drawn from one ChaCha RNG between
+/// epochs, so with an unlimited deadline the result depends only on `seed`,
+/// the number of islands and `epochs`, not on thread scheduling.
+#[allow(clippy::too_many_arguments)]
+pub fn run_islands(
+    instance: &Instance,
+    config: &IslandConfig,
+    sa: &SaConfig,
+    tabu: &TabuConfig,
+    seed: u64,
+    bound: f64,
+    deadline: &Deadline,
+    incumbent: &Incumbent<Solution>,
+) -> ScheduleResult {
+    let (jobs, machines) = (&instance.jobs[..], &instance.machines[..]);
+    let count = config
+        .islands
+        .unwrap_or_else(rayon::current_num_threads)
+        .max(1);
+    let rules = dispatch_portfolio();
+    let mut sa = SaConfig {
+        iterations: Some(config.sa_iterations),
+        ..sa.clone()
+    };
+    let mut tabu = TabuConfig {
+        iterations: Some(config.tabu_iterations),
+        ..tabu.clone()
+    };
+
+    let mut islands: Vec<Island> = (0..count)
+        .into_par_iter()
+        .map(|i| {
+            let (rule, machine_rule) = &rules[i % rules.len()];
+            let heuristic = if i % 2 == 0 {
+                Heuristic::Sa
+            } else {
+                Heuristic::Tabu
+            };
+            let start = run_dispatcher(jobs, machines, rule.as_ref(), machine_rule.as_ref(), 0.0);
+            Island {
+                name: format!("{}+{}", start.rule_name, heuristic.name()),
+                heuristic,
+                current: start.clone(),
+                best: start,
+                pool: ElitePool::new(&EliteConfig::default(), jobs.len()),
+            }
+        })
+        .collect();
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let unlimited = deadline.remaining() == Duration::MAX;
+    let best_of = |islands: &[Island]| -> usize {
+        (0..islands.len())
+            .min_by(|&a, &b| {
+                islands[a]
+                    .best
+                    .total_tardy_work
+                    .total_cmp(&islands[b].best.total_tardy_work)
+            })
+            .expect("No islands")
+    };
+    let mut epoch = 0;
+    let mut migrations = 0;
+
+    while !deadline.expired()
+        && islands[best_of(&islands)].best.total_tardy_work > bound + IMPROVEMENT_EPS
+    {
+        if unlimited && epoch >= config.epochs {
+            break;
+        }
+        epoch += 1;
+
+        let seeds: Vec<u64> = (0..count).map(|_| rng.random()).collect();
+        let epoch_start = Instant::now();
+        islands
+            .par_iter_mut()
+            .zip(seeds)
+            .for_each(|(island, island_seed)| {
+                island.epoch(jobs, machines, &sa, &tabu, island_seed, bound, deadline)
+            });
+        let epoch_time = epoch_start.elapsed();
+        let cap = deadline.remaining().mul_f64(MAX_EPOCH_SHARE);
+        if !unlimited && epoch_time > cap {
+            let scale = cap.div_duration_f64(epoch_time);
+            let shorten = |iterations: u64| ((iterations as f64 * scale) as u64).max(1);
+            sa.iterations = sa.iterations.map(shorten);
+            tabu.iterations = tabu.iterations.map(shorten);
+        }
+
+        let best = &islands[best_of(&islands)].best;
+        incumbent.offer(best.to_solution());
+
+        // ring migration of the best schedule of every island
+        let emigrants: Vec<ScheduleResult> =
+            islands.iter().map(|island| island.best.clone()).collect();
+        for (i, island) in islands.iter_mut().enumerate() {
+            let immigrant = &emigrants[(i + count - 1) % count];
+            if immigrant.total_tardy_work < island.current.total_tardy_work - IMPROVEMENT_EPS {
+                island.current = immigrant.clone();
+                migrations += 1;
+            }
+        }
+    }
+
+    let best = islands.swap_remove(best_of(&islands)).best;
+    debug!(
+        "Islands: {} islands, {} epochs, {} migrations, best {} ({})",
+        count, epoch, migrations, best.total_tardy_work, best.rule_name
+    );
+    ScheduleResult {
+        rule_name: format!("Islands({})", best.rule_name),
+        ..best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::testing::*;
+
+    #[test]
+    fn unlimited_runs_repeat_for_a_seed() {
+        let config = IslandConfig {
+            islands: Some(4),
+            sa_iterations: 2_000,
+            tabu_iterations: 50,
+            epochs: 5,
+        };
+        let (sa, tabu) = (SaConfig::default(), TabuConfig::default());
+        for seed in 0..3 {
+            let instance = random_instance(15, 3, seed);
+            let run = || {
+                let incumbent = Incumbent::new();
+                let result = run_islands(
+                    &instance,
+                    &config,
+                    &sa,
+                    &tabu,
+                    seed,
+                    0.0,
+                    &Deadline::unlimited(),
+                    &incumbent,
+                );
+                assert_consistent(
+                    &instance.jobs,
+                    &instance.machines,
+                    &result.schedule,
+                    result.total_tardy_work,
+                );
+                result
+            };
+            let (first, second) = (run(), run());
+            assert_eq!(assignment(&first.schedule), assignment(&second.schedule));
+            assert_eq!(first.rule_name, second.rule_name);
+        }
+    }
+}
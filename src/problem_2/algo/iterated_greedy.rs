@@ -0,0 +1,148 @@
+use crate::deadline::Deadline;
+use crate::problem_2::algo::des::ScheduleResult;
+use crate::problem_2::models::*;
+use crate::problem_2::schedule::Schedule;
+use clap::Args;
+use log::debug;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::time::Duration;
+
+/// Minimal decrease of late work accepted as a new best
+const IMPROVEMENT_EPS: f64 = 1e-9;
+
+/// Parameters of the iterated greedy
+#[derive(Args, Clone)]
+pub struct IgConfig {
+    /// Jobs removed and reinserted in every iteration
+    #[arg(long = "ig-destruction", default_value_t = 4)]
+    pub destruction: usize,
+
+    /// Temperature factor of the acceptance (Ruiz and Stützle)
+    #[arg(long = "ig-temperature", default_value_t = 0.4)]
+    pub temperature: f64,
+
+    /// Number of iterations when the time budget is unlimited
+    /// (otherwise the search runs until the deadline)
+    #[arg(id = "ig_iterations", long = "ig-iterations", default_value_t = 20_000)]
+    pub iterations: u64,
+}
+
+impl Default for IgConfig {
+    fn default() -> Self {
+        IgConfig {
+            destruction: 4,
+            temperature: 0.4,
+            iterations: 20_000,
+        }
+    }
+}
+
+/// Insert every job of `order` at its cheapest position over all machines.
+/// Positions between scheduled jobs are tried too, so a job may fill the
+/// idle time left before a later release date
+fn insert_all(schedule: &mut Schedule, jobs: &[Job], machines: &[Machine], order: &[usize]) {
+    for &job_idx in order {
+        let (_, _, k, position) = schedule.best_insertion(jobs, machines, job_idx);
+        schedule.insert(jobs, machines, job_idx, k, position);
+    }
+}
+
+/// Earliest due date order inserted with `insert_all`
+pub fn construct(jobs: &[Job], machines: &[Machine]) -> Schedule {
+    let mut order: Vec<usize> = (0..jobs.len()).collect();
+    order.sort_by_key(|&idx| (jobs[idx].d_j, idx));
+    let mut schedule = Schedule::empty(machines);
+    insert_all(&mut schedule, jobs, machines, &order);
+    schedule
+}
+
+/// Iterated greedy over per-machine sequences.
+///
+/// Starts from `construct`, then repeatedly removes `destruction` random jobs
+/// and reinserts them in random order at their cheapest position over every
+/// machine. A worse schedule is accepted with probability exp(-Δ / T), where
+/// T = temperature · Σ p_j / (10 · n · m) as in the iterated greedy of Ruiz
+/// and Stützle. Stops at the deadline, the lower bound `bound`, or after `iterations`
+/// when the deadline is unlimited.
+pub fn run_iterated_greedy(
+    jobs: &[Job],
+    machines: &[Machine],
+    config: &IgConfig,
+    seed: u64,
+    bound: f64,
+    deadline: &Deadline,
+) -> ScheduleResult {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut current = construct(jobs, machines);
+    let mut best = current.clone();
+    if jobs.is_empty() {
+        return best.to_result(jobs, machines, "IG");
+    }
+    debug!("IG construction: {}", current.total_cost());
+
+    let destruction = config.destruction.clamp(1, jobs.len());
+    let total_work: f64 = jobs.iter().map(|job| job.p_j as f64).sum();
+    let temperature = (config.temperature * total_work
+        / (10.0 * jobs.len() as f64 * machines.len() as f64))
+        .max(f64::MIN_POSITIVE);
+    let unlimited = deadline.remaining() == Duration::MAX;
+    let mut iteration: u64 = 0;
+
+    while !deadline.expired() && best.total_cost() > bound + IMPROVEMENT_EPS {
+        if unlimited && iteration >= config.iterations {
+            break;
+        }
+        iteration += 1;
+
+        let mut candidate = current.clone();
+        let mut removed = Vec::with_capacity(destruction);
+        for _ in 0..destruction {
+            let busy: Vec<usize> = (0..machines.len())
+                .filter(|&k| !candidate.sequence(k).is_empty())
+                .collect();
+            let k = busy[rng.random_range(0..busy.len())];
+            let position = rng.random_range(0..candidate.sequence(k).len());
+            removed.push(candidate.remove(jobs, machines, k, position));
+        }
+        removed.shuffle(&mut rng);
+        insert_all(&mut candidate, jobs, machines, &removed);
+
+        let delta = candidate.total_cost() - current.total_cost();
+        if delta < 0.0 || rng.random::<f64>() < (-delta / temperature).exp() {
+            current = candidate;
+            if current.total_cost() < best.total_cost() - IMPROVEMENT_EPS {
+                best = current.clone();
+            }
+        }
+    }
+
+    debug!("IG: {} iterations, best {}", iteration, best.total_cost());
+    best.to_result(jobs, machines, "IG")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::testing::*;
+
+    #[test]
+    fn ig_improves_on_its_construction_and_repeats() {
+        let config = IgConfig {
+            iterations: 300,
+            ..IgConfig::default()
+        };
+        for seed in 0..10 {
+            let instance = random_instance(20, 3, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            let start = construct(jobs, machines).total_cost();
+            let run =
+                || run_iterated_greedy(jobs, machines, &config, seed, 0.0, &Deadline::unlimited());
+            let result = run();
+            assert_consistent(jobs, machines, &result.schedule, result.total_tardy_work);
+            assert!(result.total_tardy_work <= start + TOLERANCE);
+            assert_eq!(assignment(&run().schedule), assignment(&result.schedule));
+        }
+    }
+}
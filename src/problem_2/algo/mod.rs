@@ -1,3 +1,18 @@
+pub mod aco;
+pub mod alns;
 pub mod beam_search;
+pub mod beam_variants;
+pub mod branch_and_bound;
+pub mod brkga;
 pub mod des;
+pub mod flow;
+pub mod genetic;
+pub mod island;
+pub mod iterated_greedy;
 pub mod portfolio;
+pub mod preemptive;
+pub mod release_queue;
+pub mod resequence;
+pub mod sa;
+pub mod tabu;
+pub mod vns;
@@ -1,4 +1,9 @@
-use crate::problem_2::models::{Job, Machine, PriorityRule};
+use crate::problem_2::algo::beam_search::find_best_machine_assignment;
+use crate::problem_2::models::{Job, Machine, MachineSelectionRule, PriorityRule};
+use std::sync::Arc;
+
+/// Relative margin by which `ATC::overtake_time` is brought forward
+const OVERTAKE_MARGIN: f64 = 1e-9;
 
 #[allow(non_camel_case_types)]
 pub struct A_EDD {}
@@ -8,9 +13,13 @@ impl PriorityRule for A_EDD {
         "A-EDD"
     }
 
-    fn calculate(&self, _t_current: f64, job: &Job, machine: &Machine) -> f64 {
+    fn calculate(&self, _t_current: f64, job: &Job, _machine: &Machine) -> f64 {
         -job.d_j as f64
     }
+
+    fn is_static(&self) -> bool {
+        true
+    }
 }
 
 #[allow(non_camel_case_types)]
@@ -24,6 +33,11 @@ impl PriorityRule for A_SPT {
     fn calculate(&self, _t_current: f64, job: &Job, machine: &Machine) -> f64 {
         -job.p_j as f64 * machine.b_k
     }
+
+    /// b_k scales every job alike, so the machine does not change the ranking
+    fn is_static(&self) -> bool {
+        true
+    }
 }
 
 #[allow(non_camel_case_types)]
@@ -34,9 +48,13 @@ impl PriorityRule for A_MDD {
         "A-MDD"
     }
 
-    fn calculate(&self, _t_current: f64, job: &Job, machine: &Machine) -> f64 {
+    fn calculate(&self, _t_current: f64, job: &Job, _machine: &Machine) -> f64 {
         -job.r_j as f64
     }
+
+    fn is_static(&self) -> bool {
+        true
+    }
 }
 
 #[allow(non_camel_case_types)]
@@ -55,6 +73,34 @@ impl PriorityRule for ATC {
             job.p_j as f64 * machine.b_k / slack
         }
     }
+
+    /// With w = p_j * b_k and latest start e = d_j - w the priority w / (e - t)
+    /// grows until e and is 0 from then on, so `job` can only overtake `other`
+    /// once `other` is late or where the two curves cross
+    fn overtake_time(
+        &self,
+        t_current: f64,
+        job: &Job,
+        other: &Job,
+        machine: &Machine,
+    ) -> Option<f64> {
+        if self.calculate(t_current, other, machine) == 0.0 {
+            // both stay at 0, ranked by index
+            return None;
+        }
+        let (w_job, w_other) = (job.p_j as f64 * machine.b_k, other.p_j as f64 * machine.b_k);
+        let (e_job, e_other) = (job.d_j as f64 - w_job, other.d_j as f64 - w_other);
+        let mut overtake = e_other;
+        // `job` ranks first from w_job (e_other - s) = w_other (e_job - s) on
+        // if w_other > w_job and never again otherwise. It does not rank first
+        // now, so the crossing is not before t_current (but may be at it)
+        if self.calculate(t_current, job, machine) > 0.0 && w_other > w_job {
+            let crossing = (w_other * e_job - w_job * e_other) / (w_other - w_job);
+            overtake = overtake.min(crossing);
+        }
+        // a little early, so that rounding never makes the re-ranking late
+        Some(overtake - OVERTAKE_MARGIN * (1.0 + overtake.abs()))
+    }
 }
 
 #[allow(non_camel_case_types)]
@@ -68,4 +114,276 @@ impl PriorityRule for LS {
     fn calculate(&self, _t_current: f64, job: &Job, machine: &Machine) -> f64 {
         -(job.r_j as f64 + job.p_j as f64 * machine.b_k)
     }
+
+    /// The ranking depends on the machine speed but not on the time
+    fn overtake_time(
+        &self,
+        _t_current: f64,
+        _job: &Job,
+        _other: &Job,
+        _machine: &Machine,
+    ) -> Option<f64> {
+        None
+    }
+}
+
+/// All priority rules, in the order the solvers run them
+pub fn portfolio() -> Vec<Box<dyn PriorityRule>> {
+    vec![
+        Box::new(A_EDD {}),
+        Box::new(A_SPT {}),
+        Box::new(A_MDD {}),
+        Box::new(ATC {}),
+        Box::new(LS {}),
+    ]
+}
+
+/// Machine on which the job starts first (ties: lower index)
+pub struct EarliestStart {}
+
+impl MachineSelectionRule for EarliestStart {
+    fn name(&self) -> &str {
+        "EST"
+    }
+
+    fn select(
+        &self,
+        job: &Job,
+        _next: Option<&Job>,
+        machine_times: &[f64],
+        machines: &[Machine],
+    ) -> usize {
+        (0..machines.len())
+            .min_by(|&a, &b| {
+                let start = |k: usize| machine_times[k].max(job.r_j as f64);
+                start(a).total_cmp(&start(b))
+            })
+            .expect("Instance has no machines")
+    }
+}
+
+/// Machine on which the job completes first
+pub struct EarliestFinish {}
+
+impl MachineSelectionRule for EarliestFinish {
+    fn name(&self) -> &str {
+        "EFT"
+    }
+
+    fn select(
+        &self,
+        job: &Job,
+        _next: Option<&Job>,
+        machine_times: &[f64],
+        machines: &[Machine],
+    ) -> usize {
+        find_best_machine_assignment(job, machine_times, machines).0
+    }
+}
+
+/// Fastest of the machines that are idle when the job can start first
+pub struct FastestIdle {}
+
+impl MachineSelectionRule for FastestIdle {
+    fn name(&self) -> &str {
+        "FI"
+    }
+
+    fn select(
+        &self,
+        job: &Job,
+        _next: Option<&Job>,
+        machine_times: &[f64],
+        machines: &[Machine],
+    ) -> usize {
+        let earliest_free = machine_times.iter().copied().fold(f64::MAX, f64::min);
+        let t_start = earliest_free.max(job.r_j as f64);
+        (0..machines.len())
+            .filter(|&k| machine_times[k] <= t_start)
+            .min_by(|&a, &b| machines[a].b_k.total_cmp(&machines[b].b_k))
+            .expect("Instance has no machines")
+    }
+}
+
+/// Machine with the smallest late work of the job (ties: earliest finish)
+pub struct LeastLateWork {}
+
+impl MachineSelectionRule for LeastLateWork {
+    fn name(&self) -> &str {
+        "LLW"
+    }
+
+    fn select(
+        &self,
+        job: &Job,
+        _next: Option<&Job>,
+        machine_times: &[f64],
+        machines: &[Machine],
+    ) -> usize {
+        (0..machines.len())
+            .map(|k| {
+                let finish = job.completion_time(&machines[k], machine_times[k]);
+                (job.tardy_work(&machines[k], finish), finish, k)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)))
+            .expect("Instance has no machines")
+            .2
+    }
+}
+
+/// Machine minimising the late work of the job plus the smallest late work of
+/// the next job placed after it (ties: earliest finish); without a next job
+/// the same as `LeastLateWork`
+pub struct Lookahead {}
+
+impl MachineSelectionRule for Lookahead {
+    fn name(&self) -> &str {
+        "LA"
+    }
+
+    fn select(
+        &self,
+        job: &Job,
+        next: Option<&Job>,
+        machine_times: &[f64],
+        machines: &[Machine],
+    ) -> usize {
+        let Some(next) = next else {
+            return LeastLateWork {}.select(job, None, machine_times, machines);
+        };
+        (0..machines.len())
+            .map(|k| {
+                let finish = job.completion_time(&machines[k], machine_times[k]);
+                let next_late_work = (0..machines.len())
+                    .map(|l| {
+                        let free_time = if l == k { finish } else { machine_times[l] };
+                        let next_finish = next.completion_time(&machines[l], free_time);
+                        next.tardy_work(&machines[l], next_finish)
+                    })
+                    .fold(f64::MAX, f64::min);
+                let late_work = job.tardy_work(&machines[k], finish) + next_late_work;
+                (late_work, finish, k)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)))
+            .expect("Instance has no machines")
+            .2
+    }
+}
+
+/// All machine selection rules, in the order the solvers run them
+pub fn machine_rules() -> Vec<Box<dyn MachineSelectionRule>> {
+    vec![
+        Box::new(EarliestStart {}),
+        Box::new(EarliestFinish {}),
+        Box::new(FastestIdle {}),
+        Box::new(LeastLateWork {}),
+        Box::new(Lookahead {}),
+    ]
+}
+
+/// Every priority rule combined with every machine selection rule; the
+/// earliest start pairs come first, so the first `portfolio().len()` entries
+/// dispatch like the plain portfolio
+pub fn dispatch_portfolio() -> Vec<(Arc<dyn PriorityRule>, Arc<dyn MachineSelectionRule>)> {
+    let rules: Vec<Arc<dyn PriorityRule>> = portfolio().into_iter().map(Arc::from).collect();
+    machine_rules()
+        .into_iter()
+        .map(Arc::<dyn MachineSelectionRule>::from)
+        .flat_map(|machine_rule| {
+            rules
+                .iter()
+                .map(move |rule| (Arc::clone(rule), Arc::clone(&machine_rule)))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn job(id: usize, p_j: i64, r_j: i64, d_j: i64) -> Job {
+        Job { id, p_j, r_j, d_j }
+    }
+
+    fn machines(speeds: &[f64]) -> Vec<Machine> {
+        speeds
+            .iter()
+            .enumerate()
+            .map(|(id, &b_k)| Machine { id, b_k })
+            .collect()
+    }
+
+    #[test]
+    fn earliest_finish_waits_for_a_fast_machine() {
+        let machines = machines(&[1.0, 3.0]);
+        let job = job(0, 4, 0, 100);
+        // the fast machine frees up at 5 and finishes at 9, the idle slow one at 12
+        let times = [5.0, 0.0];
+        assert_eq!(EarliestFinish {}.select(&job, None, &times, &machines), 0);
+        assert_eq!(EarliestStart {}.select(&job, None, &times, &machines), 1);
+    }
+
+    #[test]
+    fn fastest_idle_ignores_busy_machines() {
+        let machines = machines(&[1.0, 3.0, 2.0]);
+        let job = job(0, 4, 1, 100);
+        // the job starts at 1, when the fastest machine is still busy
+        let times = [2.0, 0.0, 0.0];
+        assert_eq!(FastestIdle {}.select(&job, None, &times, &machines), 2);
+        assert_eq!(EarliestFinish {}.select(&job, None, &times, &machines), 0);
+    }
+
+    #[test]
+    fn least_late_work_prefers_a_slower_machine() {
+        let machines = machines(&[1.0, 2.1]);
+        let job = job(0, 4, 0, 6);
+        // late work 2 finishing at 8 against 2.4 / 2.1 finishing at 8.4
+        let times = [4.0, 0.0];
+        assert_eq!(LeastLateWork {}.select(&job, None, &times, &machines), 1);
+        assert_eq!(EarliestFinish {}.select(&job, None, &times, &machines), 0);
+    }
+
+    #[test]
+    fn lookahead_keeps_the_fast_machine_for_an_urgent_job() {
+        let machines = machines(&[1.0, 2.0]);
+        let relaxed = job(0, 2, 0, 100);
+        let urgent = job(1, 4, 0, 4);
+        let times = [0.0, 0.0];
+        // the first job is on time anywhere and finishes first on the fast machine
+        assert_eq!(
+            LeastLateWork {}.select(&relaxed, None, &times, &machines),
+            0
+        );
+        assert_eq!(Lookahead {}.select(&relaxed, None, &times, &machines), 0);
+        // but then the urgent job is late on either machine
+        assert_eq!(
+            Lookahead {}.select(&relaxed, Some(&urgent), &times, &machines),
+            1
+        );
+    }
+
+    #[test]
+    fn dispatch_portfolio_pairs_every_rule_with_every_machine_rule() {
+        let pairs: Vec<(String, String)> = dispatch_portfolio()
+            .iter()
+            .map(|(rule, machine_rule)| (rule.name().to_string(), machine_rule.name().to_string()))
+            .collect();
+        let rules = portfolio();
+        let machine_rules = machine_rules();
+        assert_eq!(pairs.len(), rules.len() * machine_rules.len());
+        let distinct: HashSet<&(String, String)> = pairs.iter().collect();
+        assert_eq!(distinct.len(), pairs.len());
+        for rule in &rules {
+            for machine_rule in &machine_rules {
+                let pair = (rule.name().to_string(), machine_rule.name().to_string());
+                assert!(distinct.contains(&pair), "{:?} missing", pair);
+            }
+        }
+        // the earliest start pairs dispatch like the plain portfolio
+        for (pair, rule) in pairs.iter().zip(&rules) {
+            assert_eq!((pair.0.as_str(), pair.1.as_str()), (rule.name(), "EST"));
+        }
+    }
 }
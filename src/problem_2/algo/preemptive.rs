@@ -0,0 +1,357 @@
+use crate::problem_2::algo::flow::FlowNetwork;
+use crate::problem_2::models::*;
+use log::debug;
+
+/// Largest network built for the lower bound (arcs between jobs and levels)
+pub const MAX_BOUND_ARCS: usize = 4_000_000;
+/// Remaining amounts closer than this are treated as equal levels
+const LEVEL_EPS: f64 = 1e-7;
+
+/// Federgruen-Groenevelt network of Q|r_j,pmtn|ΣY_j.
+///
+/// Time is cut at every r_j and d_j. In interval t of length L the machines,
+/// sorted by speed s_k = 1 / b_k, give levels k = 1..m: job j -> (t, k) has
+/// capacity (s_k - s_{k+1}) L and (t, k) -> sink k (s_k - s_{k+1}) L.
+/// The maximum flow is the largest amount of work done before the due dates,
+/// everything else is late.
+struct OnTimeNetwork {
+    /// interval boundaries
+    points: Vec<f64>,
+    /// machine indices from the fastest
+    machine_order: Vec<usize>,
+    /// (job, interval, arc) for every job -> level arc
+    job_arcs: Vec<(usize, usize, usize)>,
+    network: FlowNetwork,
+}
+
+/// Machines from the fastest, ties by index
+fn machines_by_speed(machines: &[Machine]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..machines.len()).collect();
+    order.sort_by(|&a, &b| machines[a].b_k.total_cmp(&machines[b].b_k).then(a.cmp(&b)));
+    order
+}
+
+/// Interval boundaries: sorted distinct release and due dates
+fn time_points(jobs: &[Job]) -> Vec<f64> {
+    let mut points: Vec<i64> = jobs.iter().flat_map(|job| [job.r_j, job.d_j]).collect();
+    points.sort_unstable();
+    points.dedup();
+    points.into_iter().map(|point| point as f64).collect()
+}
+
+/// Speed drop between consecutive machines of `machine_order` (s_k - s_{k+1})
+fn level_speeds(machines: &[Machine], machine_order: &[usize]) -> Vec<f64> {
+    let speeds: Vec<f64> = machine_order
+        .iter()
+        .map(|&k| 1.0 / machines[k].b_k)
+        .collect();
+    (0..speeds.len())
+        .map(|k| speeds[k] - speeds.get(k + 1).copied().unwrap_or(0.0))
+        .collect()
+}
+
+/// Number of job -> level arcs of the network
+fn network_arcs(jobs: &[Job], machines: &[Machine]) -> usize {
+    let points = time_points(jobs);
+    let levels = level_speeds(machines, &machines_by_speed(machines))
+        .iter()
+        .filter(|&&drop| drop > 0.0)
+        .count();
+    jobs.iter()
+        .map(|job| {
+            let from = points.partition_point(|&point| point < job.r_j as f64);
+            let to = points.partition_point(|&point| point < job.d_j as f64);
+            to.saturating_sub(from) * levels
+        })
+        .sum()
+}
+
+impl OnTimeNetwork {
+    fn new(jobs: &[Job], machines: &[Machine]) -> Self {
+        let points = time_points(jobs);
+        let machine_order = machines_by_speed(machines);
+        let drops = level_speeds(machines, &machine_order);
+        let intervals = points.len().saturating_sub(1);
+        let m = machines.len();
+
+        // 0 source, 1 sink, then jobs, then (interval, level)
+        let source = 0;
+        let sink = 1;
+        let job_node = |j: usize| 2 + j;
+        let level_node = |t: usize, k: usize| 2 + jobs.len() + t * m + k;
+        let mut network = FlowNetwork::new(2 + jobs.len() + intervals * m);
+
+        for (j, job) in jobs.iter().enumerate() {
+            network.add_arc(source, job_node(j), job.p_j as f64);
+        }
+        for t in 0..intervals {
+            let length = points[t + 1] - points[t];
+            for (k, &drop) in drops.iter().enumerate() {
+                if drop > 0.0 {
+                    network.add_arc(level_node(t, k), sink, (k + 1) as f64 * drop * length);
+                }
+            }
+        }
+        let mut job_arcs = Vec::new();
+        for (j, job) in jobs.iter().enumerate() {
+            let from = points.partition_point(|&point| point < job.r_j as f64);
+            let to = points.partition_point(|&point| point < job.d_j as f64);
+            for t in from..to {
+                let length = points[t + 1] - points[t];
+                for (k, &drop) in drops.iter().enumerate() {
+                    if drop > 0.0 {
+                        let arc = network.add_arc(job_node(j), level_node(t, k), drop * length);
+                        job_arcs.push((j, t, arc));
+                    }
+                }
+            }
+        }
+
+        OnTimeNetwork {
+            points,
+            machine_order,
+            job_arcs,
+            network,
+        }
+    }
+
+    /// Largest amount of work that can be done on time
+    fn solve(&mut self) -> f64 {
+        self.network.max_flow(0, 1)
+    }
+
+    /// Work of every job done in each interval, as `amounts[t]` = [(job, amount)]
+    fn amounts(&self) -> Vec<Vec<(usize, f64)>> {
+        let mut amounts = vec![Vec::new(); self.points.len().saturating_sub(1)];
+        for &(j, t, arc) in &self.job_arcs {
+            let flow = self.network.flow(arc);
+            if flow <= 0.0 {
+                continue;
+            }
+            match amounts[t].last_mut() {
+                Some((last, amount)) if *last == j => *amount += flow,
+                _ => amounts[t].push((j, flow)),
+            }
+        }
+        amounts
+    }
+}
+
+/// Level algorithm (Horvath, Lam, Sethi) for Q|pmtn|Cmax from `start`.
+///
+/// Jobs with the most work left run on the fastest machines; jobs at the same
+/// level share their machines, which is turned into preemptions by rotating
+/// the group over its machines in equal slots. Returns the makespan.
+fn level_schedule(
+    amounts: &[(usize, f64)],
+    machines: &[Machine],
+    machine_order: &[usize],
+    start: f64,
+    pieces: &mut Vec<Piece>,
+) -> f64 {
+    let mut remaining: Vec<(usize, f64)> = amounts
+        .iter()
+        .copied()
+        .filter(|&(_, amount)| amount > LEVEL_EPS)
+        .collect();
+    let mut time = start;
+
+    while !remaining.is_empty() {
+        remaining.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        // groups of equal level as [from, to) with their machines and rate per job
+        let mut groups: Vec<(usize, usize, usize, usize, f64)> = Vec::new();
+        let mut machine_idx = 0;
+        let mut from = 0;
+        while from < remaining.len() {
+            let mut to = from + 1;
+            while to < remaining.len() && remaining[from].1 - remaining[to].1 <= LEVEL_EPS {
+                to += 1;
+            }
+            let used = (to - from).min(machine_order.len() - machine_idx);
+            let speed: f64 = machine_order[machine_idx..machine_idx + used]
+                .iter()
+                .map(|&k| 1.0 / machines[k].b_k)
+                .sum();
+            groups.push((from, to, machine_idx, used, speed / (to - from) as f64));
+            machine_idx += used;
+            from = to;
+        }
+
+        // next event: a group catches up with the one below or runs out of work
+        let mut delta = f64::MAX;
+        for (g, &(from, _, _, _, rate)) in groups.iter().enumerate() {
+            if rate <= 0.0 {
+                continue;
+            }
+            delta = delta.min(remaining[from].1 / rate);
+            if let Some(&(below, _, _, _, below_rate)) = groups.get(g + 1) {
+                if rate > below_rate {
+                    let gap = remaining[from].1 - remaining[below].1;
+                    delta = delta.min(gap / (rate - below_rate));
+                }
+            }
+        }
+
+        for &(from, to, machine_idx, used, rate) in &groups {
+            if used == 0 {
+                continue;
+            }
+            let size = to - from;
+            let slot = delta / size as f64;
+            for i in 0..size {
+                for q in 0..used {
+                    let (job_id, _) = remaining[from + (i + q) % size];
+                    pieces.push(Piece {
+                        job_id,
+                        machine_id: machines[machine_order[machine_idx + q]].id,
+                        start: time + i as f64 * slot,
+                        end: time + (i + 1) as f64 * slot,
+                    });
+                }
+            }
+            for job in &mut remaining[from..to] {
+                job.1 -= rate * delta;
+            }
+        }
+        time += delta;
+        remaining.retain(|&(_, amount)| amount > LEVEL_EPS);
+    }
+    time
+}
+
+/// Join pieces of the same job that follow each other on a machine
+fn merge_pieces(mut pieces: Vec<Piece>) -> Vec<Piece> {
+    pieces.sort_by(|a, b| {
+        a.machine_id
+            .cmp(&b.machine_id)
+            .then(a.start.total_cmp(&b.start))
+    });
+    let mut merged: Vec<Piece> = Vec::with_capacity(pieces.len());
+    for piece in pieces {
+        match merged.last_mut() {
+            Some(last)
+                if last.machine_id == piece.machine_id
+                    && last.job_id == piece.job_id
+                    && (piece.start - last.end).abs() <= LEVEL_EPS =>
+            {
+                last.end = piece.end
+            }
+            _ => merged.push(piece),
+        }
+    }
+    merged
+}
+
+/// Optimal late work of the preemptive relaxation Q|r_j,pmtn|ΣY_j, a lower
+/// bound for problem_2. `None` when the network would exceed `MAX_BOUND_ARCS`
+pub fn preemptive_bound(jobs: &[Job], machines: &[Machine]) -> Option<f64> {
+    let arcs = network_arcs(jobs, machines);
+    if arcs > MAX_BOUND_ARCS {
+        debug!("Preemptive bound skipped, {} arcs", arcs);
+        return None;
+    }
+    let mut network = OnTimeNetwork::new(jobs, machines);
+    let on_time = network.solve();
+    let total: f64 = jobs.iter().map(|job| job.p_j as f64).sum();
+    Some((total - on_time).max(0.0))
+}
+
+/// Optimal preemptive schedule of Q|r_j,pmtn|ΣY_j.
+///
+/// The on-time work of every interval comes from the maximum flow and is laid
+/// out with the level algorithm; late work runs after the last release and
+/// due date, again with the level algorithm.
+pub fn run_preemptive(jobs: &[Job], machines: &[Machine]) -> PreemptiveSolution {
+    let mut network = OnTimeNetwork::new(jobs, machines);
+    let on_time = network.solve();
+    debug!("Preemptive on-time work: {}", on_time);
+
+    let mut pieces = Vec::new();
+    let mut done = vec![0.0; jobs.len()];
+    for (t, amounts) in network.amounts().iter().enumerate() {
+        for &(j, amount) in amounts {
+            done[j] += amount;
+        }
+        level_schedule(
+            amounts,
+            machines,
+            &network.machine_order,
+            network.points[t],
+            &mut pieces,
+        );
+    }
+
+    let late: Vec<(usize, f64)> = jobs
+        .iter()
+        .enumerate()
+        .map(|(j, job)| (j, job.p_j as f64 - done[j]))
+        .collect();
+    let last_point = network.points.last().copied().unwrap_or(0.0);
+    level_schedule(
+        &late,
+        machines,
+        &network.machine_order,
+        last_point,
+        &mut pieces,
+    );
+
+    let mut solution = PreemptiveSolution {
+        score: 0.0,
+        pieces: merge_pieces(pieces),
+    };
+    solution.score = solution.tardy_work(jobs, machines);
+    solution
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::testing::*;
+
+    #[test]
+    fn schedule_is_feasible_and_attains_bound() {
+        for seed in 0..40 {
+            let n = 3 + seed as usize % 8;
+            let m = 1 + seed as usize % 3;
+            let instance = random_instance(n, m, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            let bound = preemptive_bound(jobs, machines).expect("Network too large");
+            let solution = run_preemptive(jobs, machines);
+            assert!(
+                (solution.score - bound).abs() < TOLERANCE,
+                "seed {}: schedule {} but bound {}",
+                seed,
+                solution.score,
+                bound
+            );
+
+            let mut work = vec![0.0; n];
+            for piece in &solution.pieces {
+                assert!(piece.start >= jobs[piece.job_id].r_j as f64 - TOLERANCE);
+                assert!(piece.end >= piece.start);
+                work[piece.job_id] += (piece.end - piece.start) / machines[piece.machine_id].b_k;
+            }
+            for (job, done) in jobs.iter().zip(&work) {
+                assert!(
+                    (done - job.p_j as f64).abs() < TOLERANCE,
+                    "seed {}: job {} processed {} of {}",
+                    seed,
+                    job.id,
+                    done,
+                    job.p_j
+                );
+            }
+            // no machine and no job is in two places at once
+            for (a, first) in solution.pieces.iter().enumerate() {
+                for second in &solution.pieces[a + 1..] {
+                    let overlap =
+                        first.start.max(second.start) < first.end.min(second.end) - TOLERANCE;
+                    let shared =
+                        first.machine_id == second.machine_id || first.job_id == second.job_id;
+                    assert!(!(overlap && shared), "seed {}: overlapping pieces", seed);
+                }
+            }
+        }
+    }
+}
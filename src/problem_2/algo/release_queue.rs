@@ -0,0 +1,206 @@
+use crate::problem_2::models::{Job, Machine, PriorityRule};
+use std::cmp::Ordering;
+
+/// Released jobs of the dispatcher ranked by a priority rule on one machine.
+///
+/// Kinetic tournament tree over the job indices: every node keeps the best
+/// released job of its subtree and the time until which it stays the best,
+/// the earliest of `PriorityRule::overtake_time` of its two children's
+/// winners and the times of the children themselves. A query at time t only
+/// re-ranks the nodes whose time has passed, so static rules cost O(log n)
+/// per operation and time-dependent ones O(log n) per change of ranking.
+/// Ties go to the lower job index, as in `run_dispatcher`.
+pub struct ReleaseQueue<'a> {
+    jobs: &'a [Job],
+    machine: &'a Machine,
+    rule: &'a dyn PriorityRule,
+    /// number of leaves, a power of two
+    size: usize,
+    /// best released job of every node (root 1, leaves from `size`)
+    best: Vec<Option<usize>>,
+    /// `best` holds for queries before this time
+    valid_until: Vec<f64>,
+    len: usize,
+    /// time of the last query
+    last_query: f64,
+}
+
+impl<'a> ReleaseQueue<'a> {
+    pub fn new(jobs: &'a [Job], machine: &'a Machine, rule: &'a dyn PriorityRule) -> Self {
+        let size = jobs.len().next_power_of_two();
+        ReleaseQueue {
+            jobs,
+            machine,
+            rule,
+            size,
+            best: vec![None; 2 * size],
+            valid_until: vec![f64::INFINITY; 2 * size],
+            len: 0,
+            last_query: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, job_idx: usize) {
+        self.len += 1;
+        self.set_leaf(job_idx, Some(job_idx));
+    }
+
+    pub fn remove(&mut self, job_idx: usize) {
+        self.len -= 1;
+        self.set_leaf(job_idx, None);
+    }
+
+    /// Best and second best released job at time `t`. Going back in time
+    /// (another machine deciding before an idle one) re-ranks every subtree
+    /// holding released jobs, as the rankings only hold onward
+    pub fn best_two(&mut self, t: f64) -> (Option<usize>, Option<usize>) {
+        if t < self.last_query {
+            self.invalidate(1);
+        }
+        self.last_query = t;
+        self.refresh(1, t);
+        let Some(best) = self.best[1] else {
+            return (None, None);
+        };
+        // the second best lost to `best` somewhere on its path
+        let mut second = None;
+        let mut node = 1;
+        while node < self.size {
+            let (path, other) = if self.best[2 * node] == Some(best) {
+                (2 * node, 2 * node + 1)
+            } else {
+                (2 * node + 1, 2 * node)
+            };
+            if let Some(candidate) = self.best[other] {
+                if second.is_none_or(|second| self.ranks_above(candidate, second, t)) {
+                    second = Some(candidate);
+                }
+            }
+            node = path;
+        }
+        (Some(best), second)
+    }
+
+    fn set_leaf(&mut self, job_idx: usize, value: Option<usize>) {
+        let mut node = self.size + job_idx;
+        self.best[node] = value;
+        // ancestors are re-ranked by the next query
+        while node > 1 {
+            node /= 2;
+            self.valid_until[node] = f64::NEG_INFINITY;
+        }
+    }
+
+    fn invalidate(&mut self, node: usize) {
+        // clean empty subtrees hold no rankings
+        let dirty = self.valid_until[node] == f64::NEG_INFINITY;
+        if node >= self.size || (!dirty && self.best[node].is_none()) {
+            return;
+        }
+        self.valid_until[node] = f64::NEG_INFINITY;
+        self.invalidate(2 * node);
+        self.invalidate(2 * node + 1);
+    }
+
+    fn ranks_above(&self, a: usize, b: usize, t: f64) -> bool {
+        let priority = |idx: usize| self.rule.calculate(t, &self.jobs[idx], self.machine);
+        priority(a).total_cmp(&priority(b)).then_with(|| b.cmp(&a)) == Ordering::Greater
+    }
+
+    fn refresh(&mut self, node: usize, t: f64) {
+        if node >= self.size || self.valid_until[node] > t {
+            return;
+        }
+        let (left, right) = (2 * node, 2 * node + 1);
+        self.refresh(left, t);
+        self.refresh(right, t);
+
+        let children_valid = self.valid_until[left].min(self.valid_until[right]);
+        let (best, valid_until) = match (self.best[left], self.best[right]) {
+            (Some(a), Some(b)) => {
+                let (winner, loser) = if self.ranks_above(a, b, t) {
+                    (a, b)
+                } else {
+                    (b, a)
+                };
+                let overtake = self
+                    .rule
+                    .overtake_time(t, &self.jobs[loser], &self.jobs[winner], self.machine)
+                    .unwrap_or(f64::INFINITY);
+                (Some(winner), overtake.min(children_valid))
+            }
+            (single, None) | (None, single) => (single, children_valid),
+        };
+        self.best[node] = best;
+        self.valid_until[node] = valid_until;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::algo::portfolio::portfolio;
+    use crate::problem_2::testing::*;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    /// Best and second best of `released` at `t` by scanning them all
+    fn scan(
+        released: &[usize],
+        jobs: &[Job],
+        machine: &Machine,
+        rule: &dyn PriorityRule,
+        t: f64,
+    ) -> (Option<usize>, Option<usize>) {
+        let mut ranked: Vec<(f64, usize)> = released
+            .iter()
+            .map(|&idx| (rule.calculate(t, &jobs[idx], machine), idx))
+            .collect();
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+        (
+            ranked.first().map(|&(_, idx)| idx),
+            ranked.get(1).map(|&(_, idx)| idx),
+        )
+    }
+
+    #[test]
+    fn matches_scan_over_time() {
+        for seed in 0..50 {
+            let instance = random_instance(40, 3, seed);
+            let jobs = &instance.jobs;
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            for rule in portfolio() {
+                for machine in &instance.machines {
+                    let mut queue = ReleaseQueue::new(jobs, machine, rule.as_ref());
+                    let mut released: Vec<usize> = Vec::new();
+                    let mut pending: Vec<usize> = (0..jobs.len()).collect();
+                    let mut t: f64 = 0.0;
+                    while !pending.is_empty() || !released.is_empty() {
+                        for _ in 0..rng.random_range(0..3) {
+                            if !pending.is_empty() {
+                                let job_idx =
+                                    pending.swap_remove(rng.random_range(0..pending.len()));
+                                queue.push(job_idx);
+                                released.push(job_idx);
+                            }
+                        }
+                        // integer times land on exact ties; now and then a machine
+                        // decides before the previous one
+                        t = (t + rng.random_range(-1..4) as f64).max(0.0);
+                        let expected = scan(&released, jobs, machine, rule.as_ref(), t);
+                        assert_eq!(queue.best_two(t), expected, "seed {} {}", seed, rule.name());
+                        if let (Some(best), true) = (expected.0, rng.random_bool(0.7)) {
+                            queue.remove(best);
+                            released.retain(|&idx| idx != best);
+                        }
+                    }
+                    assert!(queue.is_empty());
+                }
+            }
+        }
+    }
+}
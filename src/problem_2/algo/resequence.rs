@@ -0,0 +1,370 @@
+use crate::deadline::Deadline;
+use crate::problem_2::algo::des::ScheduleResult;
+use crate::problem_2::models::*;
+use crate::problem_2::schedule::{Move, Schedule};
+use clap::{ArgAction, Args};
+use log::debug;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::slice;
+
+/// Minimal decrease of late work accepted as an improvement
+const IMPROVEMENT_EPS: f64 = 1e-9;
+/// Subsets of the dynamic programme between two deadline checks
+const CHECK_INTERVAL: usize = 1024;
+/// Largest number of jobs the dynamic programme accepts (job subsets are
+/// bitmasks; how many of them are reached is limited by `--reseq-max-labels`)
+const MAX_EXACT_JOBS: usize = 24;
+
+/// Parameters of the per-machine resequencing
+#[derive(Args, Clone)]
+pub struct ResequenceConfig {
+    /// Resequence the machines of the final schedule (solver only)
+    #[arg(long = "resequence", default_value_t = true, action = ArgAction::Set)]
+    pub enabled: bool,
+
+    /// Machines with at most this many jobs are resequenced exactly
+    /// (at most 24), the others by local search
+    #[arg(long = "reseq-exact-jobs", default_value_t = 16)]
+    pub exact_jobs: usize,
+
+    /// Labels the dynamic programme may keep on one machine before it gives up
+    /// and falls back to local search
+    #[arg(long = "reseq-max-labels", default_value_t = 2_000_000)]
+    pub max_labels: usize,
+
+    /// Random kicks without improvement after which the local search of a
+    /// machine stops
+    #[arg(long = "reseq-kicks", default_value_t = 50)]
+    pub kicks: usize,
+}
+
+impl Default for ResequenceConfig {
+    fn default() -> Self {
+        ResequenceConfig {
+            enabled: true,
+            exact_jobs: 16,
+            max_labels: 2_000_000,
+            kicks: 50,
+        }
+    }
+}
+
+/// Partial sequence of the dynamic programme: the jobs of its subset end at
+/// `finish` with late work `cost`; `job` was appended to label `parent` of
+/// the subset without it (one layer back)
+#[derive(Clone, Copy)]
+struct Label {
+    finish: f64,
+    cost: f64,
+    job: usize,
+    parent: usize,
+}
+
+/// Optimal order of `sequence` on `machine` (1|r_j|ΣY_j) by dynamic
+/// programming over job subsets.
+///
+/// Late work is regular, so of two partial sequences of the same jobs the one
+/// finishing no later with no more late work is never worse; every subset
+/// keeps only its Pareto front of (finish, cost). Labels are also dropped
+/// when even every remaining job on its own cannot bring them below the
+/// current order. Subsets are built layer by layer (by number of jobs) and
+/// only the reached ones are stored. Returns `None` when the deadline expires
+/// or the labels exceed `max_labels`; otherwise the best order, `sequence`
+/// itself when nothing beats it.
+fn exact_sequence(
+    jobs: &[Job],
+    machine: &Machine,
+    sequence: &[usize],
+    max_labels: usize,
+    deadline: &Deadline,
+) -> Option<Vec<usize>> {
+    let q = sequence.len();
+    let machines = slice::from_ref(machine);
+    let upper_bound = Schedule::new(jobs, machines, vec![sequence.to_vec()]).total_cost();
+    let full = (1usize << q) - 1;
+    // fronts of the reached subsets, layers[l] holding those of l jobs
+    let mut layers: Vec<HashMap<usize, Vec<Label>>> = Vec::with_capacity(q + 1);
+    let root = Label {
+        finish: 0.0,
+        cost: 0.0,
+        job: usize::MAX,
+        parent: usize::MAX,
+    };
+    layers.push(HashMap::from([(0, vec![root])]));
+    let mut total_labels = 1;
+    let mut expanded = 0usize;
+
+    for size in 0..q {
+        let layer = &layers[size];
+        let mut next: HashMap<usize, Vec<Label>> = HashMap::new();
+        // sorted, so that ties between equal labels do not depend on hashing
+        let mut masks: Vec<usize> = layer.keys().copied().collect();
+        masks.sort_unstable();
+        for mask in masks {
+            expanded += 1;
+            if expanded.is_multiple_of(CHECK_INTERVAL) && deadline.expired() {
+                return None;
+            }
+            for (label_idx, label) in layer[&mask].iter().enumerate() {
+                // late work of the remaining jobs, each on its own
+                let remaining: f64 = (0..q)
+                    .filter(|&i| mask & (1 << i) == 0)
+                    .map(|i| {
+                        let job = &jobs[sequence[i]];
+                        job.tardy_work(machine, job.completion_time(machine, label.finish))
+                    })
+                    .sum();
+                if label.cost + remaining >= upper_bound - IMPROVEMENT_EPS {
+                    continue;
+                }
+
+                for i in (0..q).filter(|&i| mask & (1 << i) == 0) {
+                    let job = &jobs[sequence[i]];
+                    let finish = job.completion_time(machine, label.finish);
+                    let cost = label.cost + job.tardy_work(machine, finish);
+                    if cost >= upper_bound - IMPROVEMENT_EPS {
+                        continue;
+                    }
+                    let front = next.entry(mask | (1 << i)).or_default();
+                    if front
+                        .iter()
+                        .any(|other| other.finish <= finish && other.cost <= cost)
+                    {
+                        continue;
+                    }
+                    let before = front.len();
+                    front.retain(|other| !(finish <= other.finish && cost <= other.cost));
+                    total_labels -= before - front.len();
+                    front.push(Label {
+                        finish,
+                        cost,
+                        job: i,
+                        parent: label_idx,
+                    });
+                    total_labels += 1;
+                    if total_labels > max_labels {
+                        return None;
+                    }
+                }
+            }
+        }
+        layers.push(next);
+    }
+
+    let Some(front) = layers[q].get(&full) else {
+        return Some(sequence.to_vec());
+    };
+    let best = (0..front.len())
+        .min_by(|&a, &b| front[a].cost.total_cmp(&front[b].cost))
+        .expect("Fronts are not empty");
+    let mut order = Vec::with_capacity(q);
+    let (mut mask, mut label_idx) = (full, best);
+    for size in (1..=q).rev() {
+        let label = layers[size][&mask][label_idx];
+        order.push(sequence[label.job]);
+        mask &= !(1 << label.job);
+        label_idx = label.parent;
+    }
+    order.reverse();
+    Some(order)
+}
+
+/// Improving moves within the single machine of `schedule` (relocations,
+/// block moves of 2-3 jobs and swaps), first improvement until none is left
+fn descend(schedule: &mut Schedule, jobs: &[Job], machines: &[Machine], deadline: &Deadline) {
+    let mut improved = true;
+    while improved && !deadline.expired() {
+        improved = false;
+        let len = schedule.sequence(0).len();
+        for block in 1..=3.min(len) {
+            for from_i in 0..=len - block {
+                for to_i in 0..=len - block {
+                    if to_i == from_i {
+                        continue;
+                    }
+                    let mv = Move::BlockMove {
+                        from_m: 0,
+                        from_i,
+                        len: block,
+                        to_m: 0,
+                        to_i,
+                    };
+                    improved |= schedule.try_move(jobs, machines, mv);
+                }
+            }
+        }
+        for i in 0..len {
+            for j in i + 1..len {
+                let mv = Move::Swap { m1: 0, i, m2: 0, j };
+                improved |= schedule.try_move(jobs, machines, mv);
+            }
+        }
+    }
+}
+
+/// Iterated local search over the order of `sequence` on `machine`: `descend`,
+/// then kicks of two random relocations followed by `descend` again, keeping
+/// the best order; stops after `kicks` kicks in a row without improvement
+fn local_search_sequence(
+    jobs: &[Job],
+    machine: &Machine,
+    sequence: &[usize],
+    kicks: usize,
+    rng: &mut ChaCha8Rng,
+    deadline: &Deadline,
+) -> Vec<usize> {
+    let machines = slice::from_ref(machine);
+    let mut best = Schedule::new(jobs, machines, vec![sequence.to_vec()]);
+    descend(&mut best, jobs, machines, deadline);
+
+    let len = sequence.len();
+    let mut failed = 0;
+    while failed < kicks && !deadline.expired() {
+        let mut current = best.clone();
+        for _ in 0..2 {
+            let mv = Move::Relocate {
+                from_m: 0,
+                from_i: rng.random_range(0..len),
+                to_m: 0,
+                to_i: rng.random_range(0..len),
+            };
+            current.apply_move(jobs, machines, mv);
+        }
+        descend(&mut current, jobs, machines, deadline);
+        if current.total_cost() < best.total_cost() - IMPROVEMENT_EPS {
+            best = current;
+            failed = 0;
+        } else {
+            failed += 1;
+        }
+    }
+    best.sequence(0).to_vec()
+}
+
+/// Per-machine resequencing of `start` with its machine assignment fixed.
+///
+/// Every machine is an independent 1|r_j|ΣY_j subproblem: machines with at
+/// most `exact_jobs` jobs get an optimal order from `exact_sequence`, the
+/// others (and those the dynamic programme gives up on) the iterated local
+/// search. Machines are processed in parallel and a machine keeps its order
+/// unless the new one has less late work, so the result is never worse
+/// than `start`.
+pub fn run_resequence(
+    jobs: &[Job],
+    machines: &[Machine],
+    start: &ScheduleResult,
+    config: &ResequenceConfig,
+    seed: u64,
+    deadline: &Deadline,
+) -> ScheduleResult {
+    let schedule = Schedule::from_schedule(jobs, machines, &start.schedule);
+    let exact_jobs = config.exact_jobs.min(MAX_EXACT_JOBS);
+
+    // (order, solved exactly)
+    let orders: Vec<(Vec<usize>, bool)> = (0..machines.len())
+        .into_par_iter()
+        .map(|k| {
+            let sequence = schedule.sequence(k);
+            if sequence.len() < 2 {
+                return (sequence.to_vec(), true);
+            }
+            if sequence.len() <= exact_jobs {
+                let exact =
+                    exact_sequence(jobs, &machines[k], sequence, config.max_labels, deadline);
+                if let Some(order) = exact {
+                    return (order, true);
+                }
+            }
+            let mut rng = ChaCha8Rng::seed_from_u64(seed.wrapping_add(k as u64));
+            let order = local_search_sequence(
+                jobs,
+                &machines[k],
+                sequence,
+                config.kicks,
+                &mut rng,
+                deadline,
+            );
+            (order, false)
+        })
+        .collect();
+
+    let exact = orders.iter().filter(|(_, exact)| *exact).count();
+    let sequences: Vec<Vec<usize>> = orders.into_iter().map(|(order, _)| order).collect();
+    let resequenced = Schedule::new(jobs, machines, sequences);
+    debug!(
+        "Resequencing: {} -> {} ({}/{} machines exact)",
+        schedule.total_cost(),
+        resequenced.total_cost(),
+        exact,
+        machines.len()
+    );
+
+    if resequenced.total_cost() < schedule.total_cost() - IMPROVEMENT_EPS {
+        resequenced.to_result(jobs, machines, &format!("{}+RS", start.rule_name))
+    } else {
+        start.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::testing::*;
+
+    #[test]
+    fn exact_sequence_matches_brute_force() {
+        for seed in 0..40 {
+            let n = 2 + seed as usize % 6;
+            let instance = random_instance(n, 3, seed);
+            let jobs = &instance.jobs;
+            let sequence: Vec<usize> = (0..n).collect();
+            for machine in &instance.machines {
+                let order =
+                    exact_sequence(jobs, machine, &sequence, usize::MAX, &Deadline::unlimited())
+                        .expect("No label limit");
+                let mut sorted = order.clone();
+                sorted.sort_unstable();
+                assert_eq!(sorted, sequence, "seed {}: not a permutation", seed);
+                let optimum = brute_force_sequence(jobs, machine, &sequence);
+                let cost = sequence_cost(jobs, machine, &order);
+                assert!(
+                    (cost - optimum).abs() < TOLERANCE,
+                    "seed {}: {} instead of {}",
+                    seed,
+                    cost,
+                    optimum
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn exact_sequence_gives_up_over_the_label_limit() {
+        let instance = random_instance(7, 1, 3);
+        let sequence: Vec<usize> = (0..7).rev().collect();
+        let machine = &instance.machines[0];
+        let limited = exact_sequence(
+            &instance.jobs,
+            machine,
+            &sequence,
+            1,
+            &Deadline::unlimited(),
+        );
+        let unlimited = exact_sequence(
+            &instance.jobs,
+            machine,
+            &sequence,
+            usize::MAX,
+            &Deadline::unlimited(),
+        );
+        assert!(
+            sequence_cost(&instance.jobs, machine, &sequence)
+                > brute_force_sequence(&instance.jobs, machine, &sequence) + TOLERANCE
+        );
+        assert!(limited.is_none());
+        assert!(unlimited.is_some());
+    }
+}
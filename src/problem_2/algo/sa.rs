@@ -0,0 +1,212 @@
+use crate::deadline::Deadline;
+use crate::problem_2::algo::des::ScheduleResult;
+use crate::problem_2::elite::ElitePool;
+use crate::problem_2::models::*;
+use crate::problem_2::schedule::{Schedule, NEIGHBOURHOODS};
+use clap::Args;
+use log::debug;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::time::Duration;
+
+/// Random moves sampled to pick the initial temperature
+const TEMPERATURE_SAMPLES: usize = 200;
+/// Iterations between two deadline checks (and temperature updates)
+const CHECK_INTERVAL: u64 = 256;
+/// Minimal decrease of late work accepted as a new best
+const IMPROVEMENT_EPS: f64 = 1e-9;
+/// Share of the budget timed to calibrate the length of a time-limited run
+const CALIBRATION_SHARE: f64 = 0.05;
+/// Share of the budget the calibrated run is planned to take, so that the
+/// deadline does not cut off the coldest part of the schedule
+const PLANNED_SHARE: f64 = 0.95;
+/// Number of moves of an unlimited run when `--sa-iterations` is not given
+const DEFAULT_ITERATIONS: u64 = 2_000_000;
+
+/// Parameters of the simulated annealing improvement phase
+#[derive(Args, Clone)]
+pub struct SaConfig {
+    /// Probability of accepting an average worsening move at the start
+    #[arg(long = "sa-initial-acceptance", default_value_t = 0.3)]
+    pub initial_acceptance: f64,
+
+    /// Final temperature as a fraction of the initial one
+    #[arg(long = "sa-final-temperature", default_value_t = 1e-3)]
+    pub final_temperature: f64,
+
+    /// Number of moves tried; calibrated from the speed of the first moves
+    /// to fill the time budget when not given. A fixed number makes runs with
+    /// the same `--seed` repeat exactly unless the deadline cuts them short
+    #[arg(id = "sa_iterations", long = "sa-iterations")]
+    pub iterations: Option<u64>,
+}
+
+impl Default for SaConfig {
+    fn default() -> Self {
+        SaConfig {
+            initial_acceptance: 0.3,
+            final_temperature: 1e-3,
+            iterations: None,
+        }
+    }
+}
+
+/// Temperature at which an average worsening move is accepted with
+/// probability `initial_acceptance`
+fn initial_temperature(
+    current: &Schedule,
+    jobs: &[Job],
+    machines: &[Machine],
+    config: &SaConfig,
+    rng: &mut ChaCha8Rng,
+) -> f64 {
+    let mut worsening = Vec::new();
+    for _ in 0..TEMPERATURE_SAMPLES {
+        let neighbourhood = NEIGHBOURHOODS[rng.random_range(0..NEIGHBOURHOODS.len())];
+        if let Some(mv) = current.random_move(neighbourhood, rng) {
+            let delta = current.move_delta(jobs, machines, mv);
+            if delta > IMPROVEMENT_EPS {
+                worsening.push(delta);
+            }
+        }
+    }
+    if worsening.is_empty() {
+        return 1.0;
+    }
+    let mean = worsening.iter().sum::<f64>() / worsening.len() as f64;
+    -mean / config.initial_acceptance.clamp(1e-6, 1.0 - 1e-6).ln()
+}
+
+/// Simulated annealing over per-machine sequences starting from `start`.
+///
+/// Moves are drawn from the VNS neighbourhoods (swap and relocate within and
+/// across machines, 2-opt, block moves). The temperature cools geometrically
+/// with the share of `iterations` done. Without `iterations` the count is
+/// calibrated once from the moves done in the first `CALIBRATION_SHARE` of
+/// the time budget (`DEFAULT_ITERATIONS` when the deadline is unlimited).
+/// Stops early once the lower bound `bound` is reached. New best schedules
+/// are pushed to `pool` (at most once per check interval). Reproducible for a
+/// given `seed` unless the length is calibrated or the deadline cuts the run
+/// short.
+#[allow(clippy::too_many_arguments)]
+pub fn run_sa(
+    jobs: &[Job],
+    machines: &[Machine],
+    start: &ScheduleResult,
+    config: &SaConfig,
+    seed: u64,
+    pool: &ElitePool,
+    bound: f64,
+    deadline: &Deadline,
+) -> ScheduleResult {
+    let rule_name = format!("{}+SA", start.rule_name);
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut current = Schedule::from_schedule(jobs, machines, &start.schedule);
+    let mut best = current.clone();
+    if jobs.is_empty() {
+        return best.to_result(jobs, machines, &rule_name);
+    }
+
+    let initial = initial_temperature(&current, jobs, machines, config, &mut rng);
+    let time_budget = (deadline.remaining() != Duration::MAX).then(|| deadline.remaining());
+    let started = deadline.elapsed();
+    // the temperature stays at `initial` until the run is calibrated
+    let mut total_iterations = match (config.iterations, time_budget) {
+        (Some(iterations), _) => iterations,
+        (None, Some(_)) => u64::MAX,
+        (None, None) => DEFAULT_ITERATIONS,
+    };
+    let mut temperature = initial;
+    let mut accepted: u64 = 0;
+    let mut iteration: u64 = 0;
+    let mut unpublished = false;
+
+    loop {
+        if iteration.is_multiple_of(CHECK_INTERVAL) {
+            if unpublished {
+                pool.push(best.to_result(jobs, machines, &rule_name).to_solution());
+                unpublished = false;
+            }
+            if deadline.expired() || best.total_cost() <= bound + IMPROVEMENT_EPS {
+                break;
+            }
+            if let Some(budget) = time_budget.filter(|_| total_iterations == u64::MAX) {
+                let used = (deadline.elapsed() - started).div_duration_f64(budget);
+                if used >= CALIBRATION_SHARE {
+                    total_iterations = (iteration as f64 * PLANNED_SHARE / used) as u64;
+                    debug!("SA calibrated to {} iterations", total_iterations);
+                }
+            }
+            let progress = iteration as f64 / total_iterations as f64;
+            if progress >= 1.0 {
+                break;
+            }
+            temperature = initial * config.final_temperature.powf(progress);
+        }
+        iteration += 1;
+
+        let neighbourhood = NEIGHBOURHOODS[rng.random_range(0..NEIGHBOURHOODS.len())];
+        let Some(mv) = current.random_move(neighbourhood, &mut rng) else {
+            continue;
+        };
+        let delta = current.move_delta(jobs, machines, mv);
+        if delta <= 0.0 || rng.random::<f64>() < (-delta / temperature).exp() {
+            current.apply_move(jobs, machines, mv);
+            accepted += 1;
+            if current.total_cost() < best.total_cost() - IMPROVEMENT_EPS {
+                best = current.clone();
+                unpublished = true;
+            }
+        }
+    }
+
+    debug!(
+        "SA: {} iterations, {} accepted, initial temperature {}, {} -> {}",
+        iteration,
+        accepted,
+        initial,
+        start.total_tardy_work,
+        best.total_cost()
+    );
+    let result = best.to_result(jobs, machines, &rule_name);
+    pool.push(result.to_solution());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::algo::des::best_dispatch;
+    use crate::problem_2::elite::EliteConfig;
+    use crate::problem_2::testing::*;
+
+    #[test]
+    fn sa_improves_on_its_start_and_repeats() {
+        let config = SaConfig {
+            iterations: Some(20_000),
+            ..SaConfig::default()
+        };
+        for seed in 0..10 {
+            let instance = random_instance(20, 3, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            let start = best_dispatch(jobs, machines, 0.0);
+            let run = || {
+                let pool = ElitePool::new(&EliteConfig::default(), jobs.len());
+                run_sa(
+                    jobs,
+                    machines,
+                    &start,
+                    &config,
+                    seed,
+                    &pool,
+                    0.0,
+                    &Deadline::unlimited(),
+                )
+            };
+            let result = run();
+            assert_consistent(jobs, machines, &result.schedule, result.total_tardy_work);
+            assert!(result.total_tardy_work <= start.total_tardy_work + TOLERANCE);
+            assert_eq!(assignment(&run().schedule), assignment(&result.schedule));
+        }
+    }
+}
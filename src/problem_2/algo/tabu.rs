@@ -0,0 +1,274 @@
+use crate::deadline::Deadline;
+use crate::problem_2::algo::des::ScheduleResult;
+use crate::problem_2::elite::ElitePool;
+use crate::problem_2::models::*;
+use crate::problem_2::schedule::{Move, Neighbourhood, Schedule};
+use clap::Args;
+use log::debug;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::time::Duration;
+
+/// Minimal decrease of late work accepted as a new best
+const IMPROVEMENT_EPS: f64 = 1e-9;
+
+/// Iterations when neither `--tabu-iterations` nor a time budget is given
+const DEFAULT_ITERATIONS: u64 = 20_000;
+
+/// Parameters of the tabu search
+#[derive(Args, Clone)]
+pub struct TabuConfig {
+    /// Moves sampled from the neighbourhood in every iteration
+    #[arg(long = "tabu-candidates", default_value_t = 100)]
+    pub candidates: usize,
+
+    /// Base number of iterations a (job, machine) attribute stays tabu
+    #[arg(long = "tabu-tenure", default_value_t = 10)]
+    pub tenure: u64,
+
+    /// Restart from a perturbed elite after this many iterations without improvement
+    #[arg(long = "tabu-restart-after", default_value_t = 500)]
+    pub restart_after: u64,
+
+    /// Random moves applied to the best schedule on a restart
+    #[arg(long = "tabu-restart-moves", default_value_t = 10)]
+    pub restart_moves: usize,
+
+    /// Number of iterations; when not given the search runs until the
+    /// deadline (`DEFAULT_ITERATIONS` when the time budget is unlimited)
+    #[arg(id = "tabu_iterations", long = "tabu-iterations")]
+    pub iterations: Option<u64>,
+}
+
+impl Default for TabuConfig {
+    fn default() -> Self {
+        TabuConfig {
+            candidates: 100,
+            tenure: 10,
+            restart_after: 500,
+            restart_moves: 10,
+            iterations: None,
+        }
+    }
+}
+
+/// Jobs moved by `mv` with the machine each of them ends up on
+fn moved_jobs(current: &Schedule, mv: Move) -> Vec<(usize, usize)> {
+    match mv {
+        Move::Swap { m1, i, m2, j } => {
+            vec![(current.sequence(m1)[i], m2), (current.sequence(m2)[j], m1)]
+        }
+        Move::Relocate {
+            from_m,
+            from_i,
+            to_m,
+            ..
+        } => vec![(current.sequence(from_m)[from_i], to_m)],
+        Move::TwoOpt { m, i, j } => (i..=j).map(|p| (current.sequence(m)[p], m)).collect(),
+        Move::BlockMove {
+            from_m,
+            from_i,
+            len,
+            to_m,
+            ..
+        } => current.sequence(from_m)[from_i..from_i + len]
+            .iter()
+            .map(|&job| (job, to_m))
+            .collect(),
+    }
+}
+
+/// Tabu list over (job, machine) attributes: after a job leaves a machine
+/// (or moves within it) it may not be placed there again until the tenure ends
+struct TabuList {
+    machines: usize,
+    /// iteration until which (job, machine) is tabu
+    until: Vec<u64>,
+}
+
+impl TabuList {
+    fn new(jobs: usize, machines: usize) -> Self {
+        TabuList {
+            machines,
+            until: vec![0; jobs * machines],
+        }
+    }
+
+    fn is_tabu(&self, moved: &[(usize, usize)], iteration: u64) -> bool {
+        moved
+            .iter()
+            .any(|&(job, machine)| self.until[job * self.machines + machine] > iteration)
+    }
+
+    fn forbid(&mut self, job: usize, machine: usize, until: u64) {
+        self.until[job * self.machines + machine] = until;
+    }
+
+    fn clear(&mut self) {
+        self.until.fill(0);
+    }
+}
+
+/// Machine of every job in `current`
+fn job_machines(current: &Schedule, jobs: usize) -> Vec<usize> {
+    let mut machine_of = vec![0; jobs];
+    for (k, sequence) in current.sequences().iter().enumerate() {
+        for &job in sequence {
+            machine_of[job] = k;
+        }
+    }
+    machine_of
+}
+
+/// Random relocation of a job to any machine and position, or a swap of
+/// two jobs on different machines
+fn random_move(current: &Schedule, rng: &mut ChaCha8Rng) -> Option<Move> {
+    if rng.random_bool(0.5) {
+        current.random_move(Neighbourhood::Relocate, rng)
+    } else {
+        current
+            .random_move(Neighbourhood::Swap, rng)
+            .filter(|mv| matches!(mv, Move::Swap { m1, m2, .. } if m1 != m2))
+    }
+}
+
+/// Tabu search over per-machine sequences starting from `start`.
+///
+/// Every iteration samples `candidates` relocate / cross-machine swap moves and
+/// applies the best one that is not tabu, or a tabu one that gives a new best
+/// (aspiration). Moving a job off a machine makes (job, machine) tabu for a
+/// randomised tenure. New best schedules are pushed to `pool`; after
+/// `restart_after` iterations without improvement the search restarts from a
+/// perturbed random elite of the pool (or the best schedule) with an empty
+/// tabu list. Runs `iterations` iterations unless the deadline comes first;
+/// reproducible for a given `seed` when the deadline does not cut it short.
+/// Schedules are scored by `Schedule`, like `Solution::calculate_score`.
+#[allow(clippy::too_many_arguments)]
+pub fn run_tabu(
+    jobs: &[Job],
+    machines: &[Machine],
+    start: &ScheduleResult,
+    config: &TabuConfig,
+    seed: u64,
+    pool: &ElitePool,
+    bound: f64,
+    deadline: &Deadline,
+) -> ScheduleResult {
+    let rule_name = format!("{}+Tabu", start.rule_name);
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut current = Schedule::from_schedule(jobs, machines, &start.schedule);
+    let mut best = current.clone();
+    if jobs.is_empty() {
+        return best.to_result(jobs, machines, &rule_name);
+    }
+
+    let unlimited = deadline.remaining() == Duration::MAX;
+    let max_iterations = config
+        .iterations
+        .or(unlimited.then_some(DEFAULT_ITERATIONS))
+        .unwrap_or(u64::MAX);
+    let mut tabu = TabuList::new(jobs.len(), machines.len());
+    let mut machine_of = job_machines(&current, jobs.len());
+    let mut iteration: u64 = 0;
+    let mut last_improvement: u64 = 0;
+    let mut restarts = 0;
+
+    while !deadline.expired() && best.total_cost() > bound + IMPROVEMENT_EPS {
+        if iteration >= max_iterations {
+            break;
+        }
+        iteration += 1;
+
+        // best admissible move among the sampled candidates
+        let mut chosen: Option<(f64, Move)> = None;
+        for _ in 0..config.candidates.max(1) {
+            let Some(mv) = random_move(&current, &mut rng) else {
+                continue;
+            };
+            let delta = current.move_delta(jobs, machines, mv);
+            if chosen
+                .as_ref()
+                .is_some_and(|(best_delta, ..)| delta >= *best_delta)
+            {
+                continue;
+            }
+            let aspiration = current.total_cost() + delta < best.total_cost() - IMPROVEMENT_EPS;
+            if aspiration || !tabu.is_tabu(&moved_jobs(&current, mv), iteration) {
+                chosen = Some((delta, mv));
+            }
+        }
+
+        if let Some((_, mv)) = chosen {
+            let moved = moved_jobs(&current, mv);
+            for &(job, _) in &moved {
+                let tenure = config.tenure + rng.random_range(0..=config.tenure / 2);
+                tabu.forbid(job, machine_of[job], iteration + tenure);
+            }
+            for &(job, machine) in &moved {
+                machine_of[job] = machine;
+            }
+            current.apply_move(jobs, machines, mv);
+
+            if current.total_cost() < best.total_cost() - IMPROVEMENT_EPS {
+                best = current.clone();
+                last_improvement = iteration;
+                pool.push(best.to_result(jobs, machines, &rule_name).to_solution());
+            }
+        }
+
+        // diversification
+        if iteration - last_improvement >= config.restart_after {
+            current = match pool.sample(&mut rng) {
+                Some(elite) => Schedule::from_schedule(jobs, machines, &elite.job_results),
+                None => best.clone(),
+            };
+            current.shake(jobs, machines, config.restart_moves, &mut rng);
+            machine_of = job_machines(&current, jobs.len());
+            tabu.clear();
+            last_improvement = iteration;
+            restarts += 1;
+        }
+    }
+
+    debug!(
+        "Tabu: {} iterations, {} restarts, {} -> {}",
+        iteration,
+        restarts,
+        start.total_tardy_work,
+        best.total_cost()
+    );
+    best.to_result(jobs, machines, &rule_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::algo::des::best_dispatch;
+    use crate::problem_2::elite::EliteConfig;
+    use crate::problem_2::testing::*;
+
+    #[test]
+    fn tabu_improves_on_its_start_and_repeats() {
+        // short stagnation so that the runs restart from the pool
+        let config = TabuConfig {
+            candidates: 20,
+            restart_after: 50,
+            iterations: Some(500),
+            ..TabuConfig::default()
+        };
+        for seed in 0..10 {
+            let instance = random_instance(20, 3, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            let start = best_dispatch(jobs, machines, 0.0);
+            let run = || {
+                let pool = ElitePool::new(&EliteConfig::default(), jobs.len());
+                let deadline = Deadline::unlimited();
+                run_tabu(jobs, machines, &start, &config, seed, &pool, 0.0, &deadline)
+            };
+            let result = run();
+            assert_consistent(jobs, machines, &result.schedule, result.total_tardy_work);
+            assert!(result.total_tardy_work <= start.total_tardy_work + TOLERANCE);
+            assert_eq!(assignment(&run().schedule), assignment(&result.schedule));
+        }
+    }
+}
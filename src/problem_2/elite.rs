@@ -0,0 +1,359 @@
+use crate::deadline::Deadline;
+use crate::problem_2::models::*;
+use crate::problem_2::schedule::{Move, Schedule};
+use clap::Args;
+use log::debug;
+use rand::Rng;
+use std::sync::Mutex;
+
+/// Minimal decrease of late work accepted as an improvement
+const IMPROVEMENT_EPS: f64 = 1e-9;
+
+/// Parameters of the elite solution pool
+#[derive(Args, Clone)]
+pub struct EliteConfig {
+    /// Number of solutions kept in the elite pool
+    #[arg(long = "elite-size", default_value_t = 10)]
+    pub size: usize,
+
+    /// Smallest distance to the other elites, as a fraction of the number of
+    /// jobs, for a solution that is not a new best to enter the pool
+    #[arg(long = "elite-min-distance", default_value_t = 0.05)]
+    pub min_distance: f64,
+}
+
+impl Default for EliteConfig {
+    fn default() -> Self {
+        EliteConfig {
+            size: 10,
+            min_distance: 0.05,
+        }
+    }
+}
+
+/// Solution with the (machine, position) of every job
+struct Elite {
+    solution: Solution,
+    placement: Vec<(usize, usize)>,
+}
+
+/// (machine, position on the machine) of every job of `job_results`,
+/// which list the jobs of every machine in processing order
+fn placement(job_results: &[JobResult]) -> Vec<(usize, usize)> {
+    let mut placement = vec![(0, 0); job_results.len()];
+    let mut next_position = Vec::new();
+    for result in job_results {
+        if next_position.len() <= result.machine_id {
+            next_position.resize(result.machine_id + 1, 0);
+        }
+        placement[result.job_id] = (result.machine_id, next_position[result.machine_id]);
+        next_position[result.machine_id] += 1;
+    }
+    placement
+}
+
+/// Jobs on a different machine plus jobs at a different position
+fn distance(a: &[(usize, usize)], b: &[(usize, usize)]) -> usize {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| usize::from(a.0 != b.0) + usize::from(a.1 != b.1))
+        .sum()
+}
+
+/// The k best mutually different solutions seen during a run.
+///
+/// Shared between solvers like `Incumbent`: any algorithm may `push` the
+/// schedules it finds and `sample` elites to restart from or to relink.
+/// A solution enters when it is a new best, or when it is far enough from
+/// every elite (`min_distance`) and either the pool is not full or it beats
+/// the worst elite; then it replaces the most similar worse elite.
+pub struct ElitePool {
+    capacity: usize,
+    min_distance: usize,
+    elites: Mutex<Vec<Elite>>,
+}
+
+impl ElitePool {
+    pub fn new(config: &EliteConfig, jobs: usize) -> Self {
+        ElitePool {
+            capacity: config.size.max(1),
+            min_distance: (config.min_distance * jobs as f64).ceil() as usize,
+            elites: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Offer `solution` to the pool, returns whether it was kept
+    pub fn push(&self, solution: Solution) -> bool {
+        let placement = placement(&solution.job_results);
+        let mut elites = self.elites.lock().unwrap();
+        let distances: Vec<usize> = elites
+            .iter()
+            .map(|elite| distance(&elite.placement, &placement))
+            .collect();
+        if distances.contains(&0) {
+            return false;
+        }
+        let new_best = elites
+            .iter()
+            .all(|elite| solution.score < elite.solution.score - IMPROVEMENT_EPS);
+        if !new_best && distances.iter().any(|&d| d < self.min_distance) {
+            return false;
+        }
+
+        let elite = Elite {
+            solution,
+            placement,
+        };
+        if elites.len() < self.capacity {
+            elites.push(elite);
+        } else {
+            // most similar elite among the worse ones
+            let Some(replaced) = (0..elites.len())
+                .filter(|&idx| elites[idx].solution.score > elite.solution.score + IMPROVEMENT_EPS)
+                .min_by_key(|&idx| distances[idx])
+            else {
+                return false;
+            };
+            elites[replaced] = elite;
+        }
+        elites.sort_by(|a, b| a.solution.score.total_cmp(&b.solution.score));
+        debug!(
+            "Elite pool: {} solutions, best {}",
+            elites.len(),
+            elites[0].solution.score
+        );
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.elites.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Best elite
+    pub fn best(&self) -> Option<Solution> {
+        let elites = self.elites.lock().unwrap();
+        elites.first().map(|elite| elite.solution.clone())
+    }
+
+    /// Uniformly random elite
+    pub fn sample(&self, rng: &mut impl Rng) -> Option<Solution> {
+        let elites = self.elites.lock().unwrap();
+        if elites.is_empty() {
+            return None;
+        }
+        Some(elites[rng.random_range(0..elites.len())].solution.clone())
+    }
+
+    /// All elites, best first
+    pub fn solutions(&self) -> Vec<Solution> {
+        let elites = self.elites.lock().unwrap();
+        elites.iter().map(|elite| elite.solution.clone()).collect()
+    }
+}
+
+/// Path relinking from `initial` towards `guide`.
+///
+/// Every step considers each job that is not yet where it is in `guide` and
+/// relocates it to its guide machine and position; the cheapest such move is
+/// applied (even when it worsens the schedule) and the job stays fixed. The
+/// walk takes at most n steps and returns the best intermediate schedule, not
+/// counting the two ends, or `None` when there is none or it is worse than
+/// the better of the two ends.
+pub fn path_relink(
+    jobs: &[Job],
+    machines: &[Machine],
+    initial: &Schedule,
+    guide: &Schedule,
+    deadline: &Deadline,
+) -> Option<Schedule> {
+    let mut target = vec![(0, 0); jobs.len()];
+    for (k, sequence) in guide.sequences().iter().enumerate() {
+        for (i, &job_idx) in sequence.iter().enumerate() {
+            target[job_idx] = (k, i);
+        }
+    }
+
+    let mut current = initial.clone();
+    let mut fixed = vec![false; jobs.len()];
+    let mut best: Option<Schedule> = None;
+    let mut steps = 0;
+    while !deadline.expired() {
+        // (delta, move, job)
+        let mut chosen: Option<(f64, Move, usize)> = None;
+        for (k, sequence) in current.sequences().iter().enumerate() {
+            for (i, &job_idx) in sequence.iter().enumerate() {
+                let (to_m, to_i) = target[job_idx];
+                if fixed[job_idx] || (k, i) == (to_m, to_i) {
+                    continue;
+                }
+                let len_after_removal = current.sequence(to_m).len() - usize::from(k == to_m);
+                let mv = Move::Relocate {
+                    from_m: k,
+                    from_i: i,
+                    to_m,
+                    to_i: to_i.min(len_after_removal),
+                };
+                let delta = current.move_delta(jobs, machines, mv);
+                if chosen.is_none_or(|(best_delta, ..)| delta < best_delta) {
+                    chosen = Some((delta, mv, job_idx));
+                }
+            }
+        }
+        let Some((_, mv, job_idx)) = chosen else {
+            break;
+        };
+        current.apply_move(jobs, machines, mv);
+        fixed[job_idx] = true;
+        steps += 1;
+
+        let is_guide = current.sequences() == guide.sequences();
+        if !is_guide
+            && best
+                .as_ref()
+                .is_none_or(|best| current.total_cost() < best.total_cost())
+        {
+            best = Some(current.clone());
+        }
+    }
+
+    debug!(
+        "Path relinking: {} steps, {} -> {}, best {:?}",
+        steps,
+        initial.total_cost(),
+        guide.total_cost(),
+        best.as_ref().map(|best| best.total_cost())
+    );
+    let endpoint_cost = initial.total_cost().min(guide.total_cost());
+    best.filter(|best| best.total_cost() <= endpoint_cost)
+}
+
+/// Relink the best elite with every other one in both directions and offer
+/// the best intermediate schedules back to the pool. Returns the best
+/// schedule found, if any of them beats the best elite
+pub fn relink_pool(
+    jobs: &[Job],
+    machines: &[Machine],
+    pool: &ElitePool,
+    deadline: &Deadline,
+) -> Option<Schedule> {
+    let elites: Vec<Schedule> = pool
+        .solutions()
+        .iter()
+        .map(|solution| Schedule::from_schedule(jobs, machines, &solution.job_results))
+        .collect();
+    let (best_elite, others) = elites.split_first()?;
+
+    let mut best: Option<Schedule> = None;
+    for other in others {
+        for (initial, guide) in [(best_elite, other), (other, best_elite)] {
+            if deadline.expired() {
+                break;
+            }
+            let Some(relinked) = path_relink(jobs, machines, initial, guide, deadline) else {
+                continue;
+            };
+            pool.push(relinked.to_result(jobs, machines, "PR").to_solution());
+            if relinked.total_cost() < best_elite.total_cost() - IMPROVEMENT_EPS
+                && best
+                    .as_ref()
+                    .is_none_or(|best| relinked.total_cost() < best.total_cost())
+            {
+                best = Some(relinked);
+            }
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::algo::des::run_dispatcher;
+    use crate::problem_2::algo::portfolio::dispatch_portfolio;
+    use crate::problem_2::testing::*;
+
+    /// Greedy schedules of every pair of the dispatch portfolio
+    fn dispatch_schedules(jobs: &[Job], machines: &[Machine]) -> Vec<Schedule> {
+        dispatch_portfolio()
+            .iter()
+            .map(|(rule, machine_rule)| {
+                let result =
+                    run_dispatcher(jobs, machines, rule.as_ref(), machine_rule.as_ref(), 0.0);
+                Schedule::from_schedule(jobs, machines, &result.schedule)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn path_relink_gives_valid_schedules() {
+        let mut relinked_paths = 0;
+        for seed in 0..20 {
+            let instance = random_instance(10, 3, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            let schedules = dispatch_schedules(jobs, machines);
+            for initial in &schedules {
+                for guide in &schedules {
+                    let relinked =
+                        path_relink(jobs, machines, initial, guide, &Deadline::unlimited());
+                    let Some(relinked) = relinked else {
+                        continue;
+                    };
+                    assert!(relinked.sequences() != guide.sequences());
+                    let endpoint_cost = initial.total_cost().min(guide.total_cost());
+                    assert!(relinked.total_cost() <= endpoint_cost, "seed {}", seed);
+                    let result = relinked.to_result(jobs, machines, "PR");
+                    assert_consistent(jobs, machines, &result.schedule, relinked.total_cost());
+                    relinked_paths += 1;
+                }
+            }
+        }
+        assert!(relinked_paths > 0);
+    }
+
+    #[test]
+    fn relink_pool_only_returns_improvements() {
+        for seed in 0..20 {
+            let instance = random_instance(10, 3, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            let pool = ElitePool::new(&EliteConfig::default(), jobs.len());
+            for schedule in dispatch_schedules(jobs, machines) {
+                pool.push(schedule.to_result(jobs, machines, "greedy").to_solution());
+            }
+            let best_elite = pool.best().expect("Pool is empty").score;
+
+            if let Some(relinked) = relink_pool(jobs, machines, &pool, &Deadline::unlimited()) {
+                // no worse than any endpoint of the paths
+                assert!(relinked.total_cost() < best_elite, "seed {}", seed);
+                let result = relinked.to_result(jobs, machines, "PR");
+                assert_consistent(jobs, machines, &result.schedule, relinked.total_cost());
+                assert!(pool.best().expect("Pool is empty").score <= relinked.total_cost());
+            }
+        }
+    }
+
+    #[test]
+    fn pool_keeps_distinct_solutions_best_first() {
+        let instance = random_instance(10, 3, 1);
+        let (jobs, machines) = (&instance.jobs, &instance.machines);
+        let pool = ElitePool::new(&EliteConfig::default(), jobs.len());
+        let solutions: Vec<Solution> = dispatch_schedules(jobs, machines)
+            .iter()
+            .map(|schedule| schedule.to_result(jobs, machines, "greedy").to_solution())
+            .collect();
+        for solution in &solutions {
+            pool.push(solution.clone());
+        }
+        // the same schedule never enters twice
+        assert!(!pool.push(pool.best().expect("Pool is empty")));
+
+        let kept = pool.solutions();
+        assert!(kept.len() <= EliteConfig::default().size);
+        assert!(kept.windows(2).all(|pair| pair[0].score <= pair[1].score));
+        let best = solutions.iter().map(|s| s.score).fold(f64::MAX, f64::min);
+        assert_eq!(kept[0].score, best);
+    }
+}
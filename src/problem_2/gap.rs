@@ -0,0 +1,125 @@
+use crate::deadline::Deadline;
+use crate::problem_2::algo::beam_search::BeamSearchConfig;
+use crate::problem_2::algo::branch_and_bound::{
+    run_branch_and_bound, ExactResult, MAX_BITMASK_JOBS,
+};
+use crate::problem_2::algo::des::{run_beam_search, run_dispatcher, ScheduleResult};
+use crate::problem_2::algo::portfolio::*;
+use crate::problem_2::lower_bound::lower_bound;
+use crate::problem_2::models::*;
+use crate::{format_gap, SchedulableProblem};
+use clap::Parser;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Optimality gap report for problem_2 heuristics
+///
+/// Solves small instances exactly and compares `run_dispatcher` and
+/// `run_beam_search` of every dispatch portfolio pair against the optimum;
+/// when the exact solver does not finish, against the best known schedule
+#[derive(Parser)]
+#[command(version, about, long_about)]
+struct GapArgs {
+    /// Instance files (12-15 jobs is practical, larger ones are not proven)
+    #[arg(required = true)]
+    instances: Vec<PathBuf>,
+
+    /// Time limit of the exact solver per instance in seconds
+    #[arg(short, long, default_value_t = 60.0)]
+    time_limit: f64,
+
+    #[command(flatten)]
+    beam: BeamSearchConfig,
+}
+
+pub fn run_gap_report() {
+    let args = GapArgs::parse();
+
+    let portfolio = dispatch_portfolio();
+
+    println!("instance\tmethod\trule\tscore\tgap");
+    for path in &args.instances {
+        let instance = Instance::from_file(path).expect("Failed to load problem from file");
+        let bound = lower_bound(&instance.jobs, &instance.machines);
+
+        let mut heuristics: Vec<(&str, ScheduleResult)> = Vec::new();
+        for (rule, machine_rule) in &portfolio {
+            let greedy = run_dispatcher(
+                &instance.jobs,
+                &instance.machines,
+                rule.as_ref(),
+                machine_rule.as_ref(),
+                0.0,
+            );
+            heuristics.push(("simulation", greedy));
+            let beam = run_beam_search(
+                &instance.jobs,
+                &instance.machines,
+                rule.as_ref(),
+                machine_rule.as_ref(),
+                &args.beam,
+                bound.value,
+                &Deadline::unlimited(),
+            );
+            heuristics.push(("beam", beam));
+        }
+
+        let upper_bound = heuristics
+            .iter()
+            .map(|(_, result)| result)
+            .min_by(|a, b| a.total_tardy_work.total_cmp(&b.total_tardy_work))
+            .expect("No heuristic results");
+        let exact = if instance.n <= MAX_BITMASK_JOBS {
+            let deadline = Deadline::after(Duration::from_secs_f64(args.time_limit));
+            run_branch_and_bound(
+                &instance.jobs,
+                &instance.machines,
+                upper_bound,
+                bound.value,
+                &deadline,
+            )
+        } else {
+            ExactResult {
+                result: upper_bound.clone(),
+                optimal: false,
+                nodes: 0,
+            }
+        };
+        let optimum = exact.result.total_tardy_work;
+        let name = path.display();
+
+        println!(
+            "{}\t{}\t-\t{:.2}\t{} ({} nodes)",
+            name,
+            if exact.optimal {
+                "optimum"
+            } else {
+                "best known"
+            },
+            optimum,
+            if exact.optimal {
+                "proven"
+            } else {
+                "not proven"
+            },
+            exact.nodes
+        );
+        println!(
+            "{}\tlower bound\t{}\t{:.2}\t{}",
+            name,
+            bound.name,
+            bound.value,
+            format_gap(bound.value, optimum)
+        );
+        for (method, result) in &heuristics {
+            println!(
+                "{}\t{}\t{}\t{:.2}\t{}",
+                name,
+                method,
+                result.rule_name,
+                result.total_tardy_work,
+                format_gap(result.total_tardy_work, optimum)
+            );
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::problem_2::algo::preemptive::preemptive_bound;
+use crate::problem_2::models::*;
+use log::debug;
+
+/// Proven lower bound on total late work together with the relaxation it came from
+#[derive(Clone, Copy, Debug)]
+pub struct LowerBound {
+    pub name: &'static str,
+    pub value: f64,
+}
+
+/// Late work of `job` started at its release date on the best machine.
+/// Positive when d_j < r_j + p_j on the fastest machine
+fn job_bound(job: &Job, machines: &[Machine]) -> f64 {
+    machines
+        .iter()
+        .map(|machine| job.tardy_work(machine, job.r_j as f64 + job.p_j as f64 * machine.b_k))
+        .fold(f64::MAX, f64::min)
+}
+
+/// Every job on its own: sum of `job_bound`
+pub fn trivial_bound(jobs: &[Job], machines: &[Machine]) -> f64 {
+    jobs.iter().map(|job| job_bound(job, machines)).sum()
+}
+
+/// Energetic bound over time windows [a, t].
+///
+/// Jobs released at or after `a` with due date at most `t` do all their
+/// on-time work inside the window, where the machines deliver at most the
+/// summed speed (1 / b_k) of the fastest of them times (t - a). Work above
+/// that capacity is late. Jobs outside the window keep their `job_bound`.
+/// O(n^2) over all pairs of release and due dates.
+pub fn energy_bound(jobs: &[Job], machines: &[Machine]) -> f64 {
+    let bounds: Vec<f64> = jobs.iter().map(|job| job_bound(job, machines)).collect();
+    let total_bound: f64 = bounds.iter().sum();
+
+    // speed_prefix[c] = summed speed of the c fastest machines
+    let mut speeds: Vec<f64> = machines.iter().map(|machine| 1.0 / machine.b_k).collect();
+    speeds.sort_by(|a, b| b.total_cmp(a));
+    let mut speed_prefix = vec![0.0; speeds.len() + 1];
+    for (c, speed) in speeds.iter().enumerate() {
+        speed_prefix[c + 1] = speed_prefix[c] + speed;
+    }
+    let max_speed = speeds.first().copied().unwrap_or(0.0);
+
+    let mut by_due_date: Vec<usize> = (0..jobs.len()).collect();
+    by_due_date.sort_by_key(|&j| jobs[j].d_j);
+    let mut window_starts: Vec<i64> = jobs.iter().map(|job| job.r_j).collect();
+    window_starts.sort_unstable();
+    window_starts.dedup();
+
+    let mut best = total_bound;
+    for &a in &window_starts {
+        let mut count = 0;
+        let mut work = 0.0;
+        let mut job_capacity = 0.0;
+        let mut inside_bound = 0.0;
+        for &j in &by_due_date {
+            let job = &jobs[j];
+            if job.r_j < a {
+                continue;
+            }
+            count += 1;
+            work += job.p_j as f64;
+            // a single job runs on one machine at a time
+            job_capacity += (job.p_j as f64).min(max_speed * (job.d_j - job.r_j).max(0) as f64);
+            inside_bound += bounds[j];
+
+            let window = (job.d_j - a).max(0) as f64;
+            let capacity = job_capacity.min(speed_prefix[count.min(speeds.len())] * window);
+            let bound = total_bound - inside_bound + inside_bound.max(work - capacity);
+            best = best.max(bound);
+        }
+    }
+    best
+}
+
+/// Best of all available lower bounds
+pub fn lower_bound(jobs: &[Job], machines: &[Machine]) -> LowerBound {
+    let mut bounds = vec![
+        LowerBound {
+            name: "trivial",
+            value: trivial_bound(jobs, machines),
+        },
+        LowerBound {
+            name: "energy",
+            value: energy_bound(jobs, machines),
+        },
+    ];
+    if let Some(value) = preemptive_bound(jobs, machines) {
+        bounds.push(LowerBound {
+            name: "preemptive",
+            value,
+        });
+    }
+    for bound in &bounds {
+        debug!("Lower bound {}: {}", bound.name, bound.value);
+    }
+    bounds
+        .into_iter()
+        .max_by(|a, b| a.value.total_cmp(&b.value))
+        .expect("No lower bounds")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::testing::*;
+
+    #[test]
+    fn bounds_do_not_exceed_optimum() {
+        for seed in 0..40 {
+            let n = 3 + seed as usize % 5;
+            let m = 1 + seed as usize % 3;
+            let instance = random_instance(n, m, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            let optimum = brute_force(jobs, machines);
+
+            let trivial = trivial_bound(jobs, machines);
+            let energy = energy_bound(jobs, machines);
+            let preemptive = preemptive_bound(jobs, machines).expect("Network too large");
+            for (name, value) in [
+                ("trivial", trivial),
+                ("energy", energy),
+                ("preemptive", preemptive),
+            ] {
+                assert!(
+                    value <= optimum + TOLERANCE,
+                    "seed {}: {} bound {} above optimum {}",
+                    seed,
+                    name,
+                    value,
+                    optimum
+                );
+            }
+            assert!(energy >= trivial - TOLERANCE, "seed {}", seed);
+            let best = lower_bound(jobs, machines).value;
+            assert!((best - trivial.max(energy).max(preemptive)).abs() < TOLERANCE);
+        }
+    }
+}
@@ -1,5 +1,12 @@
 pub mod algo;
+pub mod elite;
+pub mod gap;
 pub mod generate;
+pub mod lower_bound;
 pub mod models;
+pub mod postprocess;
+pub mod schedule;
 pub mod solve;
+#[cfg(test)]
+mod testing;
 pub mod verify;
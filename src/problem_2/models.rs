@@ -1,7 +1,7 @@
 use crate::file_handler::{read_from_file, write_to_file};
+use crate::problem_2::lower_bound::lower_bound;
 use crate::{Result, SchedulableProblem, SchedulableSolution};
 use log::debug;
-use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::path::Path;
 
@@ -15,6 +15,22 @@ pub struct Job {
     pub d_j: i64, // Termin zakończenia (w skali)
 }
 
+impl Job {
+    /// Completion time of the job started as early as possible on `machine`
+    /// that becomes free at `free_time`
+    #[inline]
+    pub fn completion_time(&self, machine: &Machine, free_time: f64) -> f64 {
+        free_time.max(self.r_j as f64) + self.p_j as f64 * machine.b_k
+    }
+
+    /// Late work Y_j of the job finishing at `completion_time` on `machine`
+    #[inline]
+    pub fn tardy_work(&self, machine: &Machine, completion_time: f64) -> f64 {
+        let tardiness = (completion_time - self.d_j as f64).max(0.0);
+        tardiness.min(self.p_j as f64 * machine.b_k) / machine.b_k
+    }
+}
+
 /// Machine capable of processing jobs
 #[derive(Clone, Copy)]
 pub struct Machine {
@@ -37,38 +53,45 @@ pub struct JobResult {
     pub tardy_work: f64,
 }
 
-/// Current state of a machine in the heap
-#[derive(PartialEq)]
-pub struct MachineState {
-    /// machine identifier
-    pub machine_id: usize,
-    /// time when the machine becomes free
-    pub free_time: f64,
-}
-
-impl Eq for MachineState {}
-
-/// Min-heap based on machine free_time
-impl Ord for MachineState {
-    fn cmp(&self, other: &Self) -> Ordering {
-        other
-            .free_time
-            .total_cmp(&self.free_time)
-            .then_with(|| self.machine_id.cmp(&other.machine_id))
+/// Priority rule trait for job scheduling, jobs with higher priority go first
+pub trait PriorityRule: Send + Sync {
+    fn name(&self) -> &str;
+    /// Calculate priority of a job at current time
+    fn calculate(&self, t_current: f64, job: &Job, machine: &Machine) -> f64;
+    /// True when the ranking of jobs depends neither on the time nor on the
+    /// machine, so the dispatcher keeps one queue of released jobs for all
+    /// machines (otherwise one per machine speed)
+    fn is_static(&self) -> bool {
+        false
     }
-}
-
-impl PartialOrd for MachineState {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    /// Earliest time from `t_current` on at which `job`, ranked below `other`
+    /// at `t_current`, may rank above it on `machine`; `None` when it never
+    /// does. The dispatcher re-ranks the two only from then on, so an early
+    /// answer is always safe; by default they are re-ranked at every decision
+    fn overtake_time(
+        &self,
+        t_current: f64,
+        _job: &Job,
+        _other: &Job,
+        _machine: &Machine,
+    ) -> Option<f64> {
+        (!self.is_static()).then_some(t_current)
     }
 }
 
-/// Priority rule trait for job scheduling
-pub trait PriorityRule: Send + Sync {
+/// Machine selection rule for job scheduling, picks the machine a dispatched
+/// job goes to
+pub trait MachineSelectionRule: Send + Sync {
     fn name(&self) -> &str;
-    /// Calculate priority of a job at current time
-    fn calculate(&self, t_current: f64, job: &Job, machine: &Machine) -> f64;
+    /// Machine for `job` when machine k becomes free at `machine_times[k]`;
+    /// `next` is the job expected to be dispatched after it, if known
+    fn select(
+        &self,
+        job: &Job,
+        next: Option<&Job>,
+        machine_times: &[f64],
+        machines: &[Machine],
+    ) -> usize;
 }
 
 #[derive(Clone)]
@@ -83,6 +106,7 @@ pub struct Instance {
     pub machines: Vec<Machine>,
 }
 
+#[derive(Clone)]
 pub struct Solution {
     /// used strategy for scheduling
     pub strategy: String,
@@ -92,6 +116,43 @@ pub struct Solution {
     pub job_results: Vec<JobResult>,
 }
 
+/// Part of a job processed without interruption (preemptive schedules)
+#[derive(Clone)]
+pub struct Piece {
+    /// job identifier
+    pub job_id: usize,
+    /// machine identifier
+    pub machine_id: usize,
+    /// start of processing
+    pub start: f64,
+    /// end of processing
+    pub end: f64,
+}
+
+/// Schedule of Q|r_j,pmtn|ΣY_j
+///
+/// File format: total late work on the first line, then one line per machine
+/// with its pieces as `job:start:end` in time order.
+pub struct PreemptiveSolution {
+    /// total late work
+    pub score: f64,
+    /// processed pieces of all jobs
+    pub pieces: Vec<Piece>,
+}
+
+impl PreemptiveSolution {
+    /// Work processed after the due dates (pieces are taken as given)
+    pub fn tardy_work(&self, jobs: &[Job], machines: &[Machine]) -> f64 {
+        self.pieces
+            .iter()
+            .map(|piece| {
+                let due_date = jobs[piece.job_id].d_j as f64;
+                (piece.end - piece.start.max(due_date)).max(0.0) / machines[piece.machine_id].b_k
+            })
+            .sum()
+    }
+}
+
 impl SchedulableProblem for Instance {
     fn from_file(path: &Path) -> Result<Self> {
         // Read file content
@@ -153,18 +214,26 @@ impl SchedulableProblem for Instance {
         for machine in &self.machines {
             content.push_str(&format!("{} ", machine.b_k));
         }
-        content.push_str("\n");
+        content.push('\n');
         for job in &self.jobs {
             content.push_str(&format!("{} {} {}\n", job.p_j, job.r_j, job.d_j));
         }
         write_to_file(path, &content);
         Ok(())
     }
+
+    fn lower_bound(&self) -> Option<f64> {
+        Some(lower_bound(&self.jobs, &self.machines).value)
+    }
 }
 
 impl SchedulableSolution for Solution {
     type Problem = Instance;
 
+    fn score(&self) -> f64 {
+        self.score
+    }
+
     fn calculate_score(&self, instance: &Self::Problem) -> i64 {
         let mut total_tardy_work: f64 = 0.0;
         let mut machine_times: HashMap<usize, f64> = HashMap::new();
@@ -251,19 +320,83 @@ impl SchedulableSolution for Solution {
 
     fn to_file(&self, path: &Path) -> Result<()> {
         let mut content = String::new();
-        content.push_str(&format!("{}\n", self.score.trunc() as i64));
+        content.push_str(&format!("{}\n", self.score.round().trunc() as i64));
         let mut machine_results: HashMap<usize, Vec<usize>> = HashMap::new();
         for result in &self.job_results {
             machine_results
                 .entry(result.machine_id)
-                .or_insert_with(Vec::new)
+                .or_default()
                 .push(result.clone().job_id);
         }
-        for machine_id in 0..machine_results.len() {
-            for job_id in &machine_results[&machine_id] {
+        // machines without jobs still get their (empty) line
+        let machine_count = machine_results.keys().max().map_or(0, |id| id + 1);
+        for machine_id in 0..machine_count {
+            for job_id in machine_results.get(&machine_id).into_iter().flatten() {
                 content.push_str(&format!("{} ", job_id));
             }
-            content.push_str("\n");
+            content.push('\n');
+        }
+        write_to_file(path, &content);
+        Ok(())
+    }
+}
+
+impl SchedulableSolution for PreemptiveSolution {
+    type Problem = Instance;
+
+    fn score(&self) -> f64 {
+        self.score
+    }
+
+    fn calculate_score(&self, instance: &Self::Problem) -> i64 {
+        self.tardy_work(&instance.jobs, &instance.machines)
+            .round()
+            .trunc() as i64
+    }
+
+    fn from_file(path: &Path) -> Result<Self> {
+        let content = read_from_file(path)?;
+        let mut lines = content.lines();
+        let score = lines.next().ok_or("Empty solution file")?.trim().parse()?;
+        let mut pieces = Vec::new();
+        for (machine_id, line) in lines.enumerate() {
+            for piece in line.split_whitespace() {
+                let parts: Vec<&str> = piece.split(':').collect();
+                if parts.len() != 3 {
+                    return Err(format!("Invalid piece {:?}", piece).into());
+                }
+                pieces.push(Piece {
+                    job_id: parts[0].parse()?,
+                    machine_id,
+                    start: parts[1].parse()?,
+                    end: parts[2].parse()?,
+                });
+            }
+        }
+        Ok(PreemptiveSolution { score, pieces })
+    }
+
+    fn to_file(&self, path: &Path) -> Result<()> {
+        let mut content = format!("{:.6}\n", self.score);
+        let machine_count = self
+            .pieces
+            .iter()
+            .map(|piece| piece.machine_id + 1)
+            .max()
+            .unwrap_or(0);
+        let mut by_machine: Vec<Vec<&Piece>> = vec![Vec::new(); machine_count];
+        for piece in &self.pieces {
+            by_machine[piece.machine_id].push(piece);
+        }
+        for pieces in &mut by_machine {
+            pieces.sort_by(|a, b| a.start.total_cmp(&b.start));
+            for piece in pieces.iter() {
+                content.push_str(&format!(
+                    "{}:{:.6}:{:.6} ",
+                    piece.job_id, piece.start, piece.end
+                ));
+            }
+            content.push('\n');
         }
         write_to_file(path, &content);
         Ok(())
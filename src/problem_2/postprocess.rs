@@ -0,0 +1,94 @@
+use crate::deadline::Deadline;
+use crate::problem_2::algo::resequence::{run_resequence, ResequenceConfig};
+use crate::problem_2::lower_bound::lower_bound;
+use crate::problem_2::models::*;
+use crate::problem_2::schedule::Schedule;
+use crate::problem_2::verify::Verifier;
+use crate::{format_gap, ProblemVerifier, SchedulableProblem, SchedulableSolution};
+use clap::Parser;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+/// Per-machine resequencing of existing problem_2 solutions
+///
+/// Keeps the machine of every job and reorders the jobs of each machine,
+/// exactly when it holds few jobs and by local search otherwise. The
+/// solution is rewritten only when its late work decreases
+#[derive(Parser)]
+#[command(version, about, long_about)]
+struct PostprocessArgs {
+    /// Instance file of the solution
+    #[arg(short, long)]
+    instance_file: PathBuf,
+
+    /// Solution file to resequence
+    solution_file: PathBuf,
+
+    /// Output file (defaults to overwriting the solution file)
+    #[arg(short, long)]
+    output_file: Option<PathBuf>,
+
+    /// Time limit in seconds (unlimited when not given)
+    #[arg(short, long)]
+    time_limit: Option<f64>,
+
+    /// Seed of the local search
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    #[command(flatten)]
+    resequence: ResequenceConfig,
+}
+
+pub fn run_postprocess() {
+    let args = PostprocessArgs::parse();
+    let deadline = match args.time_limit {
+        Some(seconds) => Deadline::after(Duration::from_secs_f64(seconds)),
+        None => Deadline::unlimited(),
+    };
+
+    let instance =
+        Instance::from_file(&args.instance_file).expect("Failed to load problem from file");
+    let solution =
+        Solution::from_file(&args.solution_file).expect("Failed to load solution from file");
+    let verifier = Verifier {};
+    if !verifier.verify_solution(&instance, &solution) {
+        println!("Instance or solution is invalid");
+        process::exit(1);
+    }
+
+    let schedule =
+        Schedule::from_schedule(&instance.jobs, &instance.machines, &solution.job_results);
+    let start = schedule.to_result(&instance.jobs, &instance.machines, &solution.strategy);
+    let resequenced = run_resequence(
+        &instance.jobs,
+        &instance.machines,
+        &start,
+        &args.resequence,
+        args.seed,
+        &deadline,
+    );
+
+    let bound = lower_bound(&instance.jobs, &instance.machines).value;
+    println!(
+        "score: {} -> {}, lower_bound: {:.2}, gap: {}",
+        start.total_tardy_work,
+        resequenced.total_tardy_work,
+        bound,
+        format_gap(resequenced.total_tardy_work, bound)
+    );
+
+    // never worse than the input, so it can always be written
+    let improved = resequenced.total_tardy_work < start.total_tardy_work;
+    let output_file = args.output_file.unwrap_or(args.solution_file.clone());
+    if improved || output_file != args.solution_file {
+        resequenced
+            .to_solution()
+            .to_file(&output_file)
+            .expect("Failed to save solution to file");
+        println!("Solution saved to {:?}", output_file);
+    } else {
+        println!("No improvement, {:?} left unchanged", output_file);
+    }
+}
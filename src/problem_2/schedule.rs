@@ -0,0 +1,653 @@
+use crate::problem_2::algo::des::ScheduleResult;
+use crate::problem_2::models::*;
+use rand::Rng;
+use std::iter;
+
+/// Minimal decrease of late work accepted as an improvement
+const IMPROVEMENT_EPS: f64 = 1e-9;
+
+/// Neighbourhood structures over per-machine sequences
+#[derive(Clone, Copy)]
+pub enum Neighbourhood {
+    Swap,
+    Relocate,
+    TwoOpt,
+    BlockMove,
+}
+
+pub const NEIGHBOURHOODS: [Neighbourhood; 4] = [
+    Neighbourhood::Swap,
+    Neighbourhood::Relocate,
+    Neighbourhood::TwoOpt,
+    Neighbourhood::BlockMove,
+];
+
+/// Single move over per-machine sequences
+#[derive(Clone, Copy)]
+pub enum Move {
+    /// exchange job at `(m1, i)` with job at `(m2, j)`
+    Swap {
+        m1: usize,
+        i: usize,
+        m2: usize,
+        j: usize,
+    },
+    /// take job from `(from_m, from_i)` and insert it at `(to_m, to_i)`
+    /// (`to_i` is the position after removal)
+    Relocate {
+        from_m: usize,
+        from_i: usize,
+        to_m: usize,
+        to_i: usize,
+    },
+    /// reverse the segment `i..=j` of machine `m`
+    TwoOpt { m: usize, i: usize, j: usize },
+    /// move `len` consecutive jobs starting at `(from_m, from_i)` to `(to_m, to_i)`
+    /// (`to_i` is the position after removal)
+    BlockMove {
+        from_m: usize,
+        from_i: usize,
+        len: usize,
+        to_m: usize,
+        to_i: usize,
+    },
+}
+
+/// Job sequences of every machine with cached completion times and late work.
+///
+/// Shared evaluation of the problem_2 solvers; the verifier keeps its own
+/// recomputation. Moves are evaluated by replaying only the changed part of
+/// a machine; the replay of the untouched suffix stops as soon as a job
+/// starts at its cached start time, because everything after it is
+/// unchanged. The schedule may be partial (see `insert` and `remove`).
+#[derive(Clone)]
+pub struct Schedule {
+    /// job indices in processing order, one sequence per machine
+    sequences: Vec<Vec<usize>>,
+    /// completion time of every position
+    completion: Vec<Vec<f64>>,
+    /// late work of the first i jobs of every machine (length + 1 entries)
+    prefix: Vec<Vec<f64>>,
+    total_cost: f64,
+}
+
+impl Schedule {
+    pub fn new(jobs: &[Job], machines: &[Machine], sequences: Vec<Vec<usize>>) -> Self {
+        let mut schedule = Schedule {
+            completion: vec![Vec::new(); sequences.len()],
+            prefix: vec![vec![0.0]; sequences.len()],
+            sequences,
+            total_cost: 0.0,
+        };
+        for k in 0..machines.len() {
+            schedule.recompute(jobs, machines, k, 0);
+        }
+        schedule
+    }
+
+    /// Schedule without jobs
+    pub fn empty(machines: &[Machine]) -> Self {
+        Schedule::new(&[], machines, vec![Vec::new(); machines.len()])
+    }
+
+    /// Rebuild per-machine sequences from job results listed in processing order
+    pub fn from_schedule(jobs: &[Job], machines: &[Machine], schedule: &[JobResult]) -> Self {
+        let mut sequences = vec![Vec::new(); machines.len()];
+        for result in schedule {
+            sequences[result.machine_id].push(result.job_id);
+        }
+        Schedule::new(jobs, machines, sequences)
+    }
+
+    pub fn sequences(&self) -> &[Vec<usize>] {
+        &self.sequences
+    }
+
+    pub fn sequence(&self, machine: usize) -> &[usize] {
+        &self.sequences[machine]
+    }
+
+    /// Total late work
+    pub fn total_cost(&self) -> f64 {
+        self.total_cost
+    }
+
+    /// Late work of the jobs on `machine`
+    pub fn machine_cost(&self, machine: usize) -> f64 {
+        *self.prefix[machine].last().unwrap()
+    }
+
+    /// Completion time of the job at `position` of `machine`
+    pub fn completion_time(&self, machine: usize, position: usize) -> f64 {
+        self.completion[machine][position]
+    }
+
+    /// Time `machine` finishes its last job
+    pub fn finish_time(&self, machine: usize) -> f64 {
+        self.completion[machine].last().copied().unwrap_or(0.0)
+    }
+
+    /// Convert into the common result type (jobs listed machine by machine)
+    pub fn to_result(&self, jobs: &[Job], machines: &[Machine], rule_name: &str) -> ScheduleResult {
+        let mut schedule = Vec::with_capacity(jobs.len());
+        for (k, machine) in machines.iter().enumerate() {
+            for (i, &job_idx) in self.sequences[k].iter().enumerate() {
+                schedule.push(JobResult {
+                    job_id: jobs[job_idx].id,
+                    machine_id: machine.id,
+                    completion_time: self.completion[k][i],
+                    tardy_work: self.prefix[k][i + 1] - self.prefix[k][i],
+                });
+            }
+        }
+        ScheduleResult {
+            rule_name: rule_name.to_string(),
+            schedule,
+            total_tardy_work: self.total_cost,
+        }
+    }
+
+    /// Refresh the caches of `machine` from `position` on
+    fn recompute(&mut self, jobs: &[Job], machines: &[Machine], machine: usize, position: usize) {
+        let len = self.sequences[machine].len();
+        self.completion[machine].truncate(position);
+        self.prefix[machine].truncate(position + 1);
+        let mut free = self.completion[machine].last().copied().unwrap_or(0.0);
+        let mut cost = self.prefix[machine][position];
+        for i in position..len {
+            let job = &jobs[self.sequences[machine][i]];
+            free = job.completion_time(&machines[machine], free);
+            cost += job.tardy_work(&machines[machine], free);
+            self.completion[machine].push(free);
+            self.prefix[machine].push(cost);
+        }
+        self.total_cost = (0..self.sequences.len())
+            .map(|k| self.machine_cost(k))
+            .sum();
+    }
+
+    /// Late work and finish time of `machine` with the sequence replaced by
+    /// `sequence[..from] ++ middle ++ sequence[resume..]`
+    fn spliced(
+        &self,
+        jobs: &[Job],
+        machines: &[Machine],
+        machine: usize,
+        from: usize,
+        middle: impl Iterator<Item = usize>,
+        resume: usize,
+    ) -> (f64, f64) {
+        let sequence = &self.sequences[machine];
+        let speed = &machines[machine];
+        let mut free = if from == 0 {
+            0.0
+        } else {
+            self.completion[machine][from - 1]
+        };
+        let mut cost = self.prefix[machine][from];
+        for job_idx in middle {
+            let job = &jobs[job_idx];
+            free = job.completion_time(speed, free);
+            cost += job.tardy_work(speed, free);
+        }
+        for i in resume..sequence.len() {
+            let job = &jobs[sequence[i]];
+            let cached_free = if i == 0 {
+                0.0
+            } else {
+                self.completion[machine][i - 1]
+            };
+            if free.max(job.r_j as f64) == cached_free.max(job.r_j as f64) {
+                // same start, the rest of the machine is unchanged
+                return (
+                    cost + self.machine_cost(machine) - self.prefix[machine][i],
+                    self.finish_time(machine),
+                );
+            }
+            free = job.completion_time(speed, free);
+            cost += job.tardy_work(speed, free);
+        }
+        (cost, free)
+    }
+
+    /// Late work of `machine` with the sequence replaced by
+    /// `sequence[..from] ++ middle ++ sequence[resume..]`
+    fn spliced_cost(
+        &self,
+        jobs: &[Job],
+        machines: &[Machine],
+        machine: usize,
+        from: usize,
+        middle: impl Iterator<Item = usize>,
+        resume: usize,
+    ) -> f64 {
+        self.spliced(jobs, machines, machine, from, middle, resume)
+            .0
+    }
+
+    /// Change of total late work and of the finish time of `machine` when
+    /// `job_idx` is inserted at `position`
+    pub fn insertion_delta(
+        &self,
+        jobs: &[Job],
+        machines: &[Machine],
+        job_idx: usize,
+        machine: usize,
+        position: usize,
+    ) -> (f64, f64) {
+        let (cost, finish) = self.spliced(
+            jobs,
+            machines,
+            machine,
+            position,
+            iter::once(job_idx),
+            position,
+        );
+        (
+            cost - self.machine_cost(machine),
+            finish - self.finish_time(machine),
+        )
+    }
+
+    /// Cheapest insertion of `job_idx` on `machine` over every position as
+    /// (Δcost, Δfinish, position), ties broken by the smaller finish time
+    pub fn best_position(
+        &self,
+        jobs: &[Job],
+        machines: &[Machine],
+        job_idx: usize,
+        machine: usize,
+    ) -> (f64, f64, usize) {
+        (0..=self.sequences[machine].len())
+            .map(|position| {
+                let (cost, finish) =
+                    self.insertion_delta(jobs, machines, job_idx, machine, position);
+                (cost, finish, position)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)))
+            .expect("Position range is never empty")
+    }
+
+    /// Cheapest insertion of `job_idx` over every position of every machine
+    /// as (Δcost, Δfinish, machine, position)
+    pub fn best_insertion(
+        &self,
+        jobs: &[Job],
+        machines: &[Machine],
+        job_idx: usize,
+    ) -> (f64, f64, usize, usize) {
+        (0..machines.len())
+            .map(|k| {
+                let (cost, finish, position) = self.best_position(jobs, machines, job_idx, k);
+                (cost, finish, k, position)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)))
+            .expect("Instance has no machines")
+    }
+
+    /// Change of total late work when the job at `position` of `machine` is removed
+    pub fn removal_delta(
+        &self,
+        jobs: &[Job],
+        machines: &[Machine],
+        machine: usize,
+        position: usize,
+    ) -> f64 {
+        self.spliced_cost(
+            jobs,
+            machines,
+            machine,
+            position,
+            iter::empty(),
+            position + 1,
+        ) - self.machine_cost(machine)
+    }
+
+    /// Insert `job_idx` at `position` of `machine`
+    pub fn insert(
+        &mut self,
+        jobs: &[Job],
+        machines: &[Machine],
+        job_idx: usize,
+        machine: usize,
+        position: usize,
+    ) {
+        self.sequences[machine].insert(position, job_idx);
+        self.recompute(jobs, machines, machine, position);
+    }
+
+    /// Remove and return the job at `position` of `machine`
+    pub fn remove(
+        &mut self,
+        jobs: &[Job],
+        machines: &[Machine],
+        machine: usize,
+        position: usize,
+    ) -> usize {
+        let job_idx = self.sequences[machine].remove(position);
+        self.recompute(jobs, machines, machine, position);
+        job_idx
+    }
+
+    /// Late work of a machine after moving the block `from..from + len` to
+    /// `to` (position after removal) on the same machine
+    fn shifted_block_cost(
+        &self,
+        jobs: &[Job],
+        machines: &[Machine],
+        machine: usize,
+        from: usize,
+        len: usize,
+        to: usize,
+    ) -> f64 {
+        let sequence = &self.sequences[machine];
+        let block = &sequence[from..from + len];
+        if to <= from {
+            let middle = block.iter().chain(&sequence[to..from]).copied();
+            self.spliced_cost(jobs, machines, machine, to, middle, from + len)
+        } else {
+            let middle = sequence[from + len..to + len].iter().chain(block).copied();
+            self.spliced_cost(jobs, machines, machine, from, middle, to + len)
+        }
+    }
+
+    /// Change of total late work caused by `mv`
+    pub fn move_delta(&self, jobs: &[Job], machines: &[Machine], mv: Move) -> f64 {
+        match mv {
+            Move::Swap { m1, i, m2, j } if m1 == m2 => {
+                let (i, j) = (i.min(j), i.max(j));
+                let sequence = &self.sequences[m1];
+                let middle = iter::once(sequence[j])
+                    .chain(sequence[i + 1..j].iter().copied())
+                    .chain(iter::once(sequence[i]));
+                self.spliced_cost(jobs, machines, m1, i, middle, j + 1) - self.machine_cost(m1)
+            }
+            Move::Swap { m1, i, m2, j } => {
+                let first = iter::once(self.sequences[m2][j]);
+                let second = iter::once(self.sequences[m1][i]);
+                self.spliced_cost(jobs, machines, m1, i, first, i + 1) - self.machine_cost(m1)
+                    + self.spliced_cost(jobs, machines, m2, j, second, j + 1)
+                    - self.machine_cost(m2)
+            }
+            Move::Relocate {
+                from_m,
+                from_i,
+                to_m,
+                to_i,
+            } => self.move_delta(
+                jobs,
+                machines,
+                Move::BlockMove {
+                    from_m,
+                    from_i,
+                    len: 1,
+                    to_m,
+                    to_i,
+                },
+            ),
+            Move::TwoOpt { m, i, j } => {
+                let middle = self.sequences[m][i..=j].iter().rev().copied();
+                self.spliced_cost(jobs, machines, m, i, middle, j + 1) - self.machine_cost(m)
+            }
+            Move::BlockMove {
+                from_m,
+                from_i,
+                len,
+                to_m,
+                to_i,
+            } if from_m == to_m => {
+                self.shifted_block_cost(jobs, machines, from_m, from_i, len, to_i)
+                    - self.machine_cost(from_m)
+            }
+            Move::BlockMove {
+                from_m,
+                from_i,
+                len,
+                to_m,
+                to_i,
+            } => {
+                let block = self.sequences[from_m][from_i..from_i + len].iter().copied();
+                self.spliced_cost(jobs, machines, from_m, from_i, iter::empty(), from_i + len)
+                    - self.machine_cost(from_m)
+                    + self.spliced_cost(jobs, machines, to_m, to_i, block, to_i)
+                    - self.machine_cost(to_m)
+            }
+        }
+    }
+
+    /// Apply `mv` unconditionally
+    pub fn apply_move(&mut self, jobs: &[Job], machines: &[Machine], mv: Move) {
+        match mv {
+            Move::Swap { m1, i, m2, j } if m1 == m2 => {
+                self.sequences[m1].swap(i, j);
+                self.recompute(jobs, machines, m1, i.min(j));
+            }
+            Move::Swap { m1, i, m2, j } => {
+                let first = self.sequences[m1][i];
+                self.sequences[m1][i] = self.sequences[m2][j];
+                self.sequences[m2][j] = first;
+                self.recompute(jobs, machines, m1, i);
+                self.recompute(jobs, machines, m2, j);
+            }
+            Move::Relocate {
+                from_m,
+                from_i,
+                to_m,
+                to_i,
+            } => self.apply_move(
+                jobs,
+                machines,
+                Move::BlockMove {
+                    from_m,
+                    from_i,
+                    len: 1,
+                    to_m,
+                    to_i,
+                },
+            ),
+            Move::TwoOpt { m, i, j } => {
+                self.sequences[m][i..=j].reverse();
+                self.recompute(jobs, machines, m, i);
+            }
+            Move::BlockMove {
+                from_m,
+                from_i,
+                len,
+                to_m,
+                to_i,
+            } => {
+                let block: Vec<usize> =
+                    self.sequences[from_m].drain(from_i..from_i + len).collect();
+                self.sequences[to_m].splice(to_i..to_i, block);
+                if from_m == to_m {
+                    self.recompute(jobs, machines, from_m, from_i.min(to_i));
+                } else {
+                    self.recompute(jobs, machines, from_m, from_i);
+                    self.recompute(jobs, machines, to_m, to_i);
+                }
+            }
+        }
+    }
+
+    /// Apply `mv` when it decreases total late work
+    pub fn try_move(&mut self, jobs: &[Job], machines: &[Machine], mv: Move) -> bool {
+        if self.move_delta(jobs, machines, mv) < -IMPROVEMENT_EPS {
+            self.apply_move(jobs, machines, mv);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Random move from `neighbourhood`, `None` when the neighbourhood is empty
+    pub fn random_move(&self, neighbourhood: Neighbourhood, rng: &mut impl Rng) -> Option<Move> {
+        let m = self.sequences.len();
+        let non_empty: Vec<usize> = (0..m).filter(|&k| !self.sequences[k].is_empty()).collect();
+        if non_empty.is_empty() {
+            return None;
+        }
+        match neighbourhood {
+            Neighbourhood::Swap => {
+                let m1 = non_empty[rng.random_range(0..non_empty.len())];
+                let m2 = non_empty[rng.random_range(0..non_empty.len())];
+                let i = rng.random_range(0..self.sequences[m1].len());
+                let j = rng.random_range(0..self.sequences[m2].len());
+                (m1 != m2 || i != j).then_some(Move::Swap { m1, i, m2, j })
+            }
+            Neighbourhood::Relocate => {
+                let from_m = non_empty[rng.random_range(0..non_empty.len())];
+                let from_i = rng.random_range(0..self.sequences[from_m].len());
+                let to_m = rng.random_range(0..m);
+                let target_len = self.sequences[to_m].len() - usize::from(to_m == from_m);
+                let to_i = rng.random_range(0..=target_len);
+                Some(Move::Relocate {
+                    from_m,
+                    from_i,
+                    to_m,
+                    to_i,
+                })
+            }
+            Neighbourhood::TwoOpt => {
+                let machine = non_empty[rng.random_range(0..non_empty.len())];
+                let len = self.sequences[machine].len();
+                if len < 2 {
+                    return None;
+                }
+                let i = rng.random_range(0..len - 1);
+                let j = rng.random_range(i + 1..len);
+                Some(Move::TwoOpt { m: machine, i, j })
+            }
+            Neighbourhood::BlockMove => {
+                let from_m = non_empty[rng.random_range(0..non_empty.len())];
+                let len = rng.random_range(2..=3).min(self.sequences[from_m].len());
+                let from_i = rng.random_range(0..=self.sequences[from_m].len() - len);
+                let to_m = rng.random_range(0..m);
+                let target_len = self.sequences[to_m].len() - if to_m == from_m { len } else { 0 };
+                let to_i = rng.random_range(0..=target_len);
+                Some(Move::BlockMove {
+                    from_m,
+                    from_i,
+                    len,
+                    to_m,
+                    to_i,
+                })
+            }
+        }
+    }
+
+    /// Shaking: apply `k` random moves from randomly chosen neighbourhoods
+    pub fn shake(&mut self, jobs: &[Job], machines: &[Machine], k: usize, rng: &mut impl Rng) {
+        for _ in 0..k {
+            let neighbourhood = NEIGHBOURHOODS[rng.random_range(0..NEIGHBOURHOODS.len())];
+            if let Some(mv) = self.random_move(neighbourhood, rng) {
+                self.apply_move(jobs, machines, mv);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem_2::testing::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    /// Every job on a random machine, in random order
+    fn random_sequences(n: usize, m: usize, rng: &mut ChaCha8Rng) -> Vec<Vec<usize>> {
+        let mut sequences = vec![Vec::new(); m];
+        for j in 0..n {
+            let k = rng.random_range(0..m);
+            let position = rng.random_range(0..=sequences[k].len());
+            sequences[k].insert(position, j);
+        }
+        sequences
+    }
+
+    /// Late work of `schedule` recomputed from scratch
+    fn full_cost(schedule: &Schedule, jobs: &[Job], machines: &[Machine]) -> f64 {
+        machines
+            .iter()
+            .zip(schedule.sequences())
+            .map(|(machine, sequence)| sequence_cost(jobs, machine, sequence))
+            .sum()
+    }
+
+    #[test]
+    fn move_delta_matches_recompute() {
+        for seed in 0..20 {
+            let instance = random_instance(12, 1 + seed as usize % 3, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let sequences = random_sequences(jobs.len(), machines.len(), &mut rng);
+            let mut schedule = Schedule::new(jobs, machines, sequences);
+
+            for _ in 0..500 {
+                let neighbourhood = NEIGHBOURHOODS[rng.random_range(0..NEIGHBOURHOODS.len())];
+                let Some(mv) = schedule.random_move(neighbourhood, &mut rng) else {
+                    continue;
+                };
+                let delta = schedule.move_delta(jobs, machines, mv);
+                let mut moved = schedule.clone();
+                moved.apply_move(jobs, machines, mv);
+                let cost = full_cost(&moved, jobs, machines);
+                assert!(
+                    (moved.total_cost() - cost).abs() < TOLERANCE,
+                    "seed {}: cached {} but recomputed {}",
+                    seed,
+                    moved.total_cost(),
+                    cost
+                );
+                assert!(
+                    (schedule.total_cost() + delta - cost).abs() < TOLERANCE,
+                    "seed {}: delta {} but cost {} -> {}",
+                    seed,
+                    delta,
+                    schedule.total_cost(),
+                    cost
+                );
+                if rng.random_bool(0.5) {
+                    schedule = moved;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn insertion_and_removal_deltas_match_recompute() {
+        for seed in 0..20 {
+            let instance = random_instance(10, 1 + seed as usize % 3, seed);
+            let (jobs, machines) = (&instance.jobs, &instance.machines);
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let mut sequences = random_sequences(jobs.len(), machines.len(), &mut rng);
+            // job 0 is left out to be inserted
+            for sequence in &mut sequences {
+                sequence.retain(|&j| j != 0);
+            }
+            let schedule = Schedule::new(jobs, machines, sequences);
+
+            for k in 0..machines.len() {
+                for position in 0..=schedule.sequence(k).len() {
+                    let (delta, finish) = schedule.insertion_delta(jobs, machines, 0, k, position);
+                    let mut inserted = schedule.clone();
+                    inserted.insert(jobs, machines, 0, k, position);
+                    let cost = full_cost(&inserted, jobs, machines);
+                    assert!((schedule.total_cost() + delta - cost).abs() < TOLERANCE);
+                    assert!((inserted.total_cost() - cost).abs() < TOLERANCE);
+                    assert!(
+                        (schedule.finish_time(k) + finish - inserted.finish_time(k)).abs()
+                            < TOLERANCE
+                    );
+                }
+                for position in 0..schedule.sequence(k).len() {
+                    let delta = schedule.removal_delta(jobs, machines, k, position);
+                    let mut removed = schedule.clone();
+                    removed.remove(jobs, machines, k, position);
+                    let cost = full_cost(&removed, jobs, machines);
+                    assert!((schedule.total_cost() + delta - cost).abs() < TOLERANCE);
+                    assert!((removed.total_cost() - cost).abs() < TOLERANCE);
+                }
+            }
+        }
+    }
+}
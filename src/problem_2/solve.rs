@@ -1,34 +1,177 @@
+use crate::deadline::Deadline;
+use crate::incumbent::Incumbent;
+use crate::problem_2::algo::aco::{run_aco, AcoConfig};
+use crate::problem_2::algo::alns::{run_alns, AlnsConfig};
+use crate::problem_2::algo::beam_search::{adaptive_beam_width, BeamSearchConfig, BeamVariant};
+use crate::problem_2::algo::beam_variants::run_beam_stack;
+use crate::problem_2::algo::branch_and_bound::{run_branch_and_bound, MAX_EXACT_JOBS};
+use crate::problem_2::algo::brkga::{run_brkga, BrkgaConfig};
 use crate::problem_2::algo::des::*;
+use crate::problem_2::algo::genetic::{run_genetic, GaConfig};
+use crate::problem_2::algo::island::{run_islands, IslandConfig};
+use crate::problem_2::algo::iterated_greedy::{run_iterated_greedy, IgConfig};
 use crate::problem_2::algo::portfolio::*;
+use crate::problem_2::algo::preemptive::run_preemptive;
+use crate::problem_2::algo::resequence::{run_resequence, ResequenceConfig};
+use crate::problem_2::algo::sa::{run_sa, SaConfig};
+use crate::problem_2::algo::tabu::{run_tabu, TabuConfig};
+use crate::problem_2::algo::vns::{run_vns, VnsConfig};
+use crate::problem_2::elite::{relink_pool, EliteConfig, ElitePool};
+use crate::problem_2::lower_bound::lower_bound;
 use crate::problem_2::models::*;
 use crate::ProblemSolver;
-use log::info;
+use clap::{Args, ValueEnum};
+use log::{info, warn};
 use rayon::prelude::*;
 use std::sync::Arc;
+use std::time::Duration;
 
-pub struct Solver {}
+/// Share of the time budget available to the algorithms, the rest is
+/// left for building and writing the solution
+const TIME_BUDGET_SHARE: f64 = 0.95;
+/// Share of the algorithm time given to the construction, the rest goes to
+/// the improvement phase
+const CONSTRUCTION_SHARE: f64 = 0.6;
+/// Share of the time left after the construction given to path relinking;
+/// every relinking is O(n³), so on large instances it would use up the rest
+const RELINK_SHARE: f64 = 0.2;
+/// Share of the time left after path relinking given to the improvement
+/// phase when the schedule is resequenced afterwards
+const IMPROVEMENT_SHARE: f64 = 0.9;
 
-impl ProblemSolver<'_> for Solver {
-    type Problem = Instance;
-    type Solution = Solution;
+/// Algorithm used to build the schedule
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum Algorithm {
+    /// Beam search with pilot over the priority rule portfolio
+    #[default]
+    Beam,
+    /// Hybrid GRASP + VNS over per-machine sequences
+    Vns,
+    /// Tabu search from the best greedy portfolio schedule
+    Tabu,
+    /// Memetic algorithm over job permutations seeded from the portfolio
+    Genetic,
+    /// Biased random-key genetic algorithm decoded by the DES dispatcher
+    Brkga,
+    /// Adaptive large neighbourhood search from the best greedy portfolio schedule
+    Alns,
+    /// Iterated greedy with reinsertion at every position of every machine
+    IteratedGreedy,
+    /// Ant colony system with the portfolio rules as visibility
+    Aco,
+    /// Island model of SA and tabu search with ring migration
+    Islands,
+    /// Branch and bound, optimal for small instances (at most 12 jobs)
+    Exact,
+    /// Beam-stack search, backtracks to prove optimality on small instances
+    BeamStack,
+}
+
+/// Local search run on the best schedule of the algorithm
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum Improvement {
+    /// Keep the schedule of the algorithm
+    None,
+    /// Simulated annealing over per-machine sequences
+    #[default]
+    Sa,
+}
+
+#[derive(Args, Default)]
+pub struct Solver {
+    /// Algorithm used to build the schedule
+    #[arg(long, value_enum, default_value_t = Algorithm::Beam)]
+    pub algorithm: Algorithm,
+
+    /// Seed of randomised algorithms. Runs repeat exactly only when the
+    /// lengths that are otherwise picked from the time budget (e.g.
+    /// `--beam-width`, `--sa-iterations`) are given and the deadline does not
+    /// cut a phase short
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Jobs released this long after a machine frees up compete for it in the
+    /// greedy dispatcher (0 gives non-delay schedules)
+    #[arg(long, default_value_t = 0.0)]
+    pub lookahead: f64,
+
+    /// Improvement phase run after the algorithm
+    #[arg(long, value_enum, default_value_t = Improvement::Sa)]
+    pub improvement: Improvement,
+
+    #[command(flatten)]
+    pub beam: BeamSearchConfig,
+
+    #[command(flatten)]
+    pub vns: VnsConfig,
+
+    #[command(flatten)]
+    pub sa: SaConfig,
 
-    fn solve(&self, instance: &mut Self::Problem) -> Self::Solution {
+    #[command(flatten)]
+    pub tabu: TabuConfig,
+
+    #[command(flatten)]
+    pub ga: GaConfig,
+
+    #[command(flatten)]
+    pub brkga: BrkgaConfig,
+
+    #[command(flatten)]
+    pub alns: AlnsConfig,
+
+    #[command(flatten)]
+    pub ig: IgConfig,
+
+    #[command(flatten)]
+    pub aco: AcoConfig,
+
+    #[command(flatten)]
+    pub islands: IslandConfig,
+
+    #[command(flatten)]
+    pub elite: EliteConfig,
+
+    #[command(flatten)]
+    pub resequence: ResequenceConfig,
+}
+
+impl Solver {
+    fn run_portfolio(
+        &self,
+        instance: &Instance,
+        deadline: &Deadline,
+        incumbent: &Incumbent<Solution>,
+        pool: &ElitePool,
+        bound: f64,
+    ) -> ScheduleResult {
         let jobs_arc = Arc::new(instance.jobs.clone());
         let machines_arc = Arc::new(instance.machines.clone());
 
         // 1. Zbuduj portfel strategii
-        let portfolio: Vec<Box<dyn PriorityRule>> = vec![
-            Box::new(A_EDD {}),
-            Box::new(A_SPT {}),
-            Box::new(A_MDD {}),
-            Box::new(ATC {}),
-            Box::new(LS {}),
-        ];
+        let portfolio = dispatch_portfolio();
+
+        // Szerokość wiązki dobrana raz, wszystkie reguły dzielą wątki
+        // (poszerzanie wiązki samo rośnie do limitu czasu)
+        let mut config = self.beam.clone();
+        if config.width.is_none() && config.variant != BeamVariant::Widening {
+            let width = adaptive_beam_width(
+                &instance.jobs,
+                &instance.machines,
+                portfolio[0].0.as_ref(),
+                portfolio[0].1.as_ref(),
+                &config,
+                deadline,
+                portfolio.len(),
+            );
+            info!("Adaptive beam width: {}", width);
+            config.width = Some(width);
+        }
 
         // 2. Uruchom wszystkie heurystyki równolegle
         let results: Vec<ScheduleResult> = portfolio
             .par_iter()
-            .map(|rule| {
+            .map(|(rule, machine_rule)| {
                 // Klonowanie Arc jest tanie
                 let jobs_clone = Arc::clone(&jobs_arc);
                 let machines_clone = Arc::clone(&machines_arc);
@@ -39,15 +182,254 @@ impl ProblemSolver<'_> for Solver {
                 // run_simulation(&jobs_clone, &machines_clone, rule.as_ref())
 
                 // beam search with pilot
-                run_beam_search(&jobs_clone, &machines_clone, rule.as_ref())
+                let result = run_beam_search(
+                    &jobs_clone,
+                    &machines_clone,
+                    rule.as_ref(),
+                    machine_rule.as_ref(),
+                    &config,
+                    bound,
+                    deadline,
+                );
+                info!("{}: {}", result.rule_name, result.total_tardy_work);
+                incumbent.offer(result.to_solution());
+                pool.push(result.to_solution());
+                result
             })
             .collect();
 
         // 3. Znajdź najlepszy deterministyczny wynik
-        let best_result = results
-            .iter()
+        results
+            .into_iter()
             .min_by(|a, b| a.total_tardy_work.total_cmp(&b.total_tardy_work))
-            .expect("Symulacje nie dały żadnych wyników");
+            .expect("Symulacje nie dały żadnych wyników")
+    }
+}
+
+impl ProblemSolver<'_> for Solver {
+    type Problem = Instance;
+    type Solution = Solution;
+
+    /// Limit from problem.md: n/10 seconds
+    fn default_time_limit(&self, instance: &Self::Problem) -> Duration {
+        Duration::from_secs_f64(instance.n as f64 / 10.0)
+    }
+
+    fn solve(
+        &self,
+        instance: &mut Self::Problem,
+        deadline: &Deadline,
+        incumbent: &Incumbent<Self::Solution>,
+    ) -> Self::Solution {
+        let deadline = deadline.split(TIME_BUDGET_SHARE);
+        // the driver solves the bound already to stop at it, all phases share it
+        let bound = incumbent
+            .lower_bound()
+            .unwrap_or_else(|| lower_bound(&instance.jobs, &instance.machines).value);
+        let construction_deadline = match self.improvement {
+            Improvement::None => deadline.clone(),
+            Improvement::Sa => deadline.split(CONSTRUCTION_SHARE),
+        };
+
+        // Szybki wynik zachłanny, żeby od razu mieć poprawne rozwiązanie na dysku
+        let greedy = run_dispatcher(
+            &instance.jobs,
+            &instance.machines,
+            &A_EDD {},
+            &EarliestStart {},
+            self.lookahead,
+        );
+        incumbent.offer(greedy.to_solution());
+        let pool = ElitePool::new(&self.elite, instance.n);
+        pool.push(greedy.to_solution());
+
+        let mut optimal = false;
+        let result = match self.algorithm {
+            Algorithm::Beam => {
+                self.run_portfolio(instance, &construction_deadline, incumbent, &pool, bound)
+            }
+            Algorithm::Vns => run_vns(
+                &instance.jobs,
+                &instance.machines,
+                &self.vns,
+                self.seed,
+                bound,
+                &construction_deadline,
+            ),
+            Algorithm::Tabu => run_tabu(
+                &instance.jobs,
+                &instance.machines,
+                &best_dispatch(&instance.jobs, &instance.machines, self.lookahead),
+                &self.tabu,
+                self.seed,
+                &pool,
+                bound,
+                &construction_deadline,
+            ),
+            Algorithm::Genetic => run_genetic(
+                &instance.jobs,
+                &instance.machines,
+                &self.ga,
+                self.seed,
+                &pool,
+                bound,
+                &construction_deadline,
+            ),
+            Algorithm::Brkga => run_brkga(
+                &instance.jobs,
+                &instance.machines,
+                &self.brkga,
+                self.seed,
+                bound,
+                &construction_deadline,
+            ),
+            Algorithm::Alns => run_alns(
+                &instance.jobs,
+                &instance.machines,
+                &best_dispatch(&instance.jobs, &instance.machines, self.lookahead),
+                &self.alns,
+                self.seed,
+                bound,
+                &construction_deadline,
+            ),
+            Algorithm::IteratedGreedy => run_iterated_greedy(
+                &instance.jobs,
+                &instance.machines,
+                &self.ig,
+                self.seed,
+                bound,
+                &construction_deadline,
+            ),
+            Algorithm::Aco => run_aco(
+                &instance.jobs,
+                &instance.machines,
+                &self.aco,
+                self.seed,
+                bound,
+                &construction_deadline,
+            ),
+            Algorithm::Islands => run_islands(
+                instance,
+                &self.islands,
+                &self.sa,
+                &self.tabu,
+                self.seed,
+                bound,
+                &construction_deadline,
+                incumbent,
+            ),
+            Algorithm::Exact if instance.n <= MAX_EXACT_JOBS => {
+                let exact = run_branch_and_bound(
+                    &instance.jobs,
+                    &instance.machines,
+                    &greedy,
+                    bound,
+                    &construction_deadline,
+                );
+                info!("Optimal: {}", exact.optimal);
+                optimal = exact.optimal;
+                exact.result
+            }
+            Algorithm::BeamStack => {
+                let exact = run_beam_stack(
+                    &instance.jobs,
+                    &instance.machines,
+                    &self.beam,
+                    &best_dispatch(&instance.jobs, &instance.machines, self.lookahead),
+                    bound,
+                    &construction_deadline,
+                );
+                info!("Optimal: {}", exact.optimal);
+                optimal = exact.optimal;
+                exact.result
+            }
+            Algorithm::Exact => {
+                warn!(
+                    "Exact solver supports at most {} jobs, using beam search",
+                    MAX_EXACT_JOBS
+                );
+                self.run_portfolio(instance, &construction_deadline, incumbent, &pool, bound)
+            }
+        };
+        incumbent.offer(result.to_solution());
+        pool.push(result.to_solution());
+
+        // path relinking between the elites found so far
+        let relinked = if optimal {
+            None
+        } else {
+            let relink_deadline = deadline.split(RELINK_SHARE);
+            relink_pool(&instance.jobs, &instance.machines, &pool, &relink_deadline)
+        };
+        let result = match relinked {
+            Some(relinked) if relinked.total_cost() < result.total_tardy_work => {
+                let relinked = relinked.to_result(&instance.jobs, &instance.machines, "PR");
+                info!(
+                    "Path relinking: {} -> {}",
+                    result.total_tardy_work, relinked.total_tardy_work
+                );
+                incumbent.offer(relinked.to_solution());
+                relinked
+            }
+            _ => result,
+        };
+
+        let improvement_deadline = if self.resequence.enabled {
+            deadline.split(IMPROVEMENT_SHARE)
+        } else {
+            deadline.clone()
+        };
+        let result = match self.improvement {
+            Improvement::Sa if !optimal => {
+                let improved = run_sa(
+                    &instance.jobs,
+                    &instance.machines,
+                    &result,
+                    &self.sa,
+                    self.seed,
+                    &pool,
+                    bound,
+                    &improvement_deadline,
+                );
+                info!(
+                    "SA: {} -> {}",
+                    result.total_tardy_work, improved.total_tardy_work
+                );
+                incumbent.offer(improved.to_solution());
+                if improved.total_tardy_work < result.total_tardy_work {
+                    improved
+                } else {
+                    result
+                }
+            }
+            _ => result,
+        };
+
+        // Ostatni krok: lepsza kolejność zadań na każdej maszynie (przydział bez zmian)
+        let result = if self.resequence.enabled && !optimal {
+            let resequenced = run_resequence(
+                &instance.jobs,
+                &instance.machines,
+                &result,
+                &self.resequence,
+                self.seed,
+                &deadline,
+            );
+            info!(
+                "Resequencing: {} -> {}",
+                result.total_tardy_work, resequenced.total_tardy_work
+            );
+            incumbent.offer(resequenced.to_solution());
+            resequenced
+        } else {
+            result
+        };
+
+        let best_result = if result.total_tardy_work <= greedy.total_tardy_work {
+            result
+        } else {
+            greedy
+        };
 
         info!("Najlepsza reguła: {}", best_result.rule_name);
         info!(
@@ -55,10 +437,36 @@ impl ProblemSolver<'_> for Solver {
             best_result.total_tardy_work
         );
 
-        Solution {
-            strategy: best_result.rule_name.clone(),
-            score: best_result.total_tardy_work,
-            job_results: best_result.schedule.clone(),
-        }
+        best_result.to_solution()
+    }
+}
+
+/// Exact solver of the preemptive relaxation Q|r_j,pmtn|ΣY_j
+#[derive(Args, Default)]
+pub struct PreemptiveSolver {}
+
+impl ProblemSolver<'_> for PreemptiveSolver {
+    type Problem = Instance;
+    type Solution = PreemptiveSolution;
+
+    /// Limit from problem.md: n/10 seconds
+    fn default_time_limit(&self, instance: &Self::Problem) -> Duration {
+        Duration::from_secs_f64(instance.n as f64 / 10.0)
+    }
+
+    /// Polynomial, so the deadline is not checked
+    fn solve(
+        &self,
+        instance: &mut Self::Problem,
+        _deadline: &Deadline,
+        _incumbent: &Incumbent<Self::Solution>,
+    ) -> Self::Solution {
+        let solution = run_preemptive(&instance.jobs, &instance.machines);
+        info!(
+            "Preemptive late work: {} ({} pieces)",
+            solution.score,
+            solution.pieces.len()
+        );
+        solution
     }
 }
@@ -0,0 +1,141 @@
+//! Tiny random instances and brute-force optima for the unit tests
+use crate::problem_2::models::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::HashMap;
+
+/// Tolerance used when comparing late work
+pub const TOLERANCE: f64 = 1e-6;
+
+/// Random instance of `n` jobs on `m` machines. Machine 0 has b_k = 1, the
+/// others 1, 1.5 or 2, so both identical and slower machines occur. Due dates
+/// are tight enough that most instances have late work
+pub fn random_instance(n: usize, m: usize, seed: u64) -> Instance {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let jobs: Vec<Job> = (0..n)
+        .map(|id| {
+            let p_j = rng.random_range(1..=10);
+            let r_j = rng.random_range(0..=15);
+            let d_j = r_j + rng.random_range(0..=2 * p_j);
+            Job { id, p_j, r_j, d_j }
+        })
+        .collect();
+    let machines: Vec<Machine> = (0..m)
+        .map(|id| Machine {
+            id,
+            b_k: if id == 0 {
+                1.0
+            } else {
+                [1.0, 1.5, 2.0][rng.random_range(0..3)]
+            },
+        })
+        .collect();
+    Instance {
+        n,
+        m,
+        jobs,
+        machines,
+    }
+}
+
+/// Late work of `sequence` processed in this order on `machine`
+pub fn sequence_cost(jobs: &[Job], machine: &Machine, sequence: &[usize]) -> f64 {
+    let mut free: f64 = 0.0;
+    let mut cost = 0.0;
+    for &j in sequence {
+        let job = &jobs[j];
+        free = free.max(job.r_j as f64) + job.p_j as f64 * machine.b_k;
+        cost += job.tardy_work(machine, free);
+    }
+    cost
+}
+
+/// Every order of `items`
+pub fn permutations(items: &[usize]) -> Vec<Vec<usize>> {
+    if items.is_empty() {
+        return vec![Vec::new()];
+    }
+    let mut result = Vec::new();
+    for (i, &first) in items.iter().enumerate() {
+        let mut rest = items.to_vec();
+        rest.remove(i);
+        for mut tail in permutations(&rest) {
+            tail.insert(0, first);
+            result.push(tail);
+        }
+    }
+    result
+}
+
+/// Optimal late work of the jobs of `sequence` on `machine` over all orders
+pub fn brute_force_sequence(jobs: &[Job], machine: &Machine, sequence: &[usize]) -> f64 {
+    permutations(sequence)
+        .iter()
+        .map(|order| sequence_cost(jobs, machine, order))
+        .fold(f64::MAX, f64::min)
+}
+
+/// Optimal late work over every machine assignment and every order
+pub fn brute_force(jobs: &[Job], machines: &[Machine]) -> f64 {
+    let n = jobs.len();
+    let m = machines.len();
+    // optimum of (machine, job subset)
+    let mut memo: HashMap<(usize, usize), f64> = HashMap::new();
+    let mut best = f64::MAX;
+    for code in 0..m.pow(n as u32) {
+        let mut masks = vec![0usize; m];
+        let mut rest = code;
+        for j in 0..n {
+            masks[rest % m] |= 1 << j;
+            rest /= m;
+        }
+        let cost: f64 = masks
+            .iter()
+            .enumerate()
+            .map(|(k, &mask)| {
+                *memo.entry((k, mask)).or_insert_with(|| {
+                    let subset: Vec<usize> = (0..n).filter(|j| mask & (1 << j) != 0).collect();
+                    brute_force_sequence(jobs, &machines[k], &subset)
+                })
+            })
+            .sum();
+        best = best.min(cost);
+    }
+    best
+}
+
+/// Checks that `schedule` holds every job once and that its late work,
+/// recomputed machine by machine in the listed order, is `total`
+pub fn assert_consistent(jobs: &[Job], machines: &[Machine], schedule: &[JobResult], total: f64) {
+    let mut seen = vec![false; jobs.len()];
+    let mut sequences = vec![Vec::new(); machines.len()];
+    for result in schedule {
+        assert!(
+            !seen[result.job_id],
+            "job {} scheduled twice",
+            result.job_id
+        );
+        seen[result.job_id] = true;
+        sequences[result.machine_id].push(result.job_id);
+    }
+    assert!(seen.iter().all(|&s| s), "not every job is scheduled");
+    let cost: f64 = machines
+        .iter()
+        .zip(&sequences)
+        .map(|(machine, sequence)| sequence_cost(jobs, machine, sequence))
+        .sum();
+    assert!(
+        (cost - total).abs() < TOLERANCE,
+        "schedule has late work {} but reports {}",
+        cost,
+        total
+    );
+}
+
+/// (job, machine) pairs of `schedule` in the listed order, to compare runs
+pub fn assignment(schedule: &[JobResult]) -> Vec<(usize, usize)> {
+    schedule
+        .iter()
+        .map(|result| (result.job_id, result.machine_id))
+        .collect()
+}
@@ -5,9 +5,9 @@ use ::log::{debug, error, warn};
 
 pub struct Verifier {}
 
-fn calculate_completion_times(instance: &Instance, job_results: &Vec<JobResult>) -> Vec<JobResult> {
+fn calculate_completion_times(instance: &Instance, job_results: &[JobResult]) -> Vec<JobResult> {
     let mut machine_times: Vec<f64> = vec![0.0; instance.m];
-    let mut filled_job_results = job_results.clone();
+    let mut filled_job_results = job_results.to_vec();
 
     for result in &mut filled_job_results {
         let job = &instance.jobs[result.job_id];
@@ -122,7 +122,7 @@ impl ProblemVerifier for Verifier {
         for job_result in job_results {
             let job = &jobs[job_result.job_id];
             let duration = job.p_j as f64 * instance.machines[job_result.machine_id].b_k;
-            let job_start_time = job_result.completion_time as f64 - duration;
+            let job_start_time = job_result.completion_time - duration;
             debug!(
                 "Verifying Job {} on Machine {}: duration {} start_time {} completion_time {}",
                 job.id, job_result.machine_id, duration, job_start_time, job_result.completion_time